@@ -0,0 +1,191 @@
+use crate::engine::Options;
+use crate::transport::{Reply, TransportError};
+
+// 当前的请求间隔，展示在控制台中
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BackoffState {
+    // 每个 worker 两次请求之间的基础间隔，实际等待时间会加上随机抖动
+    pub delay_ms: u64,
+    // 连续遇到服务器过载的次数，收到正常响应后清零
+    pub overloaded: u32,
+    // 所有 worker 合计的每秒请求上限
+    pub max_rps: Option<u32>,
+}
+
+// 服务器过载时成倍放慢，收到正常响应后逐步恢复
+pub(crate) struct Backoff {
+    base_ms: u64,
+    max_ms: u64,
+    jitter_percent: u64,
+    // 相邻两个请求之间的最小间隔，由每秒请求上限换算
+    spacing_ms: Option<u64>,
+    next_slot_ms: u64,
+    rng: u64,
+    state: BackoffState,
+}
+
+impl Backoff {
+    pub(crate) fn new(options: &Options, seed: u64) -> Self {
+        let base_ms = options.interval_ms;
+        Self {
+            base_ms,
+            max_ms: options.max_interval_ms.max(base_ms),
+            jitter_percent: options.jitter_percent.min(100) as u64,
+            spacing_ms: options
+                .max_rps
+                .filter(|&rps| rps > 0)
+                .map(|rps| 1000 / rps as u64),
+            next_slot_ms: 0,
+            // xorshift 的状态不能为 0
+            rng: seed | 1,
+            state: BackoffState {
+                delay_ms: base_ms,
+                overloaded: 0,
+                max_rps: options.max_rps,
+            },
+        }
+    }
+
+    pub(crate) fn seed(&mut self, seed: u64) {
+        self.rng = seed | 1;
+    }
+
+    pub(crate) fn state(&self) -> BackoffState {
+        self.state
+    }
+
+    // 根据请求结果调整间隔，返回间隔是否有变化
+    pub(crate) fn record(&mut self, result: &Result<Reply, TransportError>) -> bool {
+        let before = self.state;
+        match result {
+            Err(e) if is_overload(e) => {
+                self.state.overloaded += 1;
+                self.state.delay_ms = (self.state.delay_ms.max(1) * 2).min(self.max_ms);
+            }
+            // 其他传输错误与服务器负载无关，保持当前间隔
+            Err(_) => {}
+            Ok(_) => {
+                self.state.overloaded = 0;
+                self.state.delay_ms = (self.state.delay_ms / 2).max(self.base_ms);
+            }
+        }
+        self.state != before
+    }
+
+    // 本次等待的时间，在基础间隔上加减随机抖动，避免各个 worker 同时发出请求
+    pub(crate) fn delay(&mut self) -> u64 {
+        let delay = self.state.delay_ms;
+        let spread = delay * self.jitter_percent / 100;
+        if spread == 0 {
+            return delay;
+        }
+        delay - spread + self.next_random() % (spread * 2 + 1)
+    }
+
+    // 为下一个请求预留发送时间，返回需要等待的毫秒数
+    pub(crate) fn reserve(&mut self, now_ms: u64) -> u64 {
+        let Some(spacing) = self.spacing_ms else {
+            return 0;
+        };
+        let slot = self.next_slot_ms.max(now_ms);
+        self.next_slot_ms = slot + spacing;
+        slot - now_ms
+    }
+
+    fn next_random(&mut self) -> u64 {
+        // xorshift64
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+}
+
+// 服务器繁忙、请求超时和连接失败说明上游已经过载
+fn is_overload(e: &TransportError) -> bool {
+    matches!(
+        e,
+        TransportError::Timeout
+            | TransportError::Connect
+            | TransportError::Http(429 | 502 | 503 | 504)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> Options {
+        Options {
+            interval_ms: 200,
+            max_interval_ms: 1000,
+            jitter_percent: 0,
+            max_rps: None,
+            ..Options::default()
+        }
+    }
+
+    fn healthy() -> Result<Reply, TransportError> {
+        Ok(Reply {
+            code: 500,
+            msg: "本轮次选课暂未开始".to_string(),
+            latency_ms: None,
+            date_ms: None,
+        })
+    }
+
+    #[test]
+    fn slows_down_on_overload_and_recovers() {
+        let mut backoff = Backoff::new(&options(), 1);
+
+        assert!(backoff.record(&Err(TransportError::Http(503))));
+        assert_eq!(backoff.state().delay_ms, 400);
+        backoff.record(&Err(TransportError::Timeout));
+        backoff.record(&Err(TransportError::Connect));
+        assert_eq!(backoff.state().delay_ms, 1000);
+        assert_eq!(backoff.state().overloaded, 3);
+
+        backoff.record(&healthy());
+        assert_eq!(backoff.state().delay_ms, 500);
+        assert_eq!(backoff.state().overloaded, 0);
+        backoff.record(&healthy());
+        backoff.record(&healthy());
+        assert_eq!(backoff.state().delay_ms, 200);
+        assert!(!backoff.record(&healthy()));
+    }
+
+    #[test]
+    fn unrelated_errors_keep_the_interval() {
+        let mut backoff = Backoff::new(&options(), 1);
+        assert!(!backoff.record(&Err(TransportError::Tls)));
+        assert!(!backoff.record(&Err(TransportError::Http(404))));
+        assert_eq!(backoff.state().delay_ms, 200);
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let options = Options {
+            jitter_percent: 25,
+            ..options()
+        };
+        let mut backoff = Backoff::new(&options, 42);
+        let delays: Vec<u64> = (0..200).map(|_| backoff.delay()).collect();
+        assert!(delays.iter().all(|d| (150..=250).contains(d)));
+        assert!(delays.iter().any(|&d| d != delays[0]));
+    }
+
+    #[test]
+    fn reserves_evenly_spaced_slots() {
+        let options = Options {
+            max_rps: Some(10),
+            ..options()
+        };
+        let mut backoff = Backoff::new(&options, 1);
+        assert_eq!(backoff.reserve(1000), 0);
+        assert_eq!(backoff.reserve(1000), 100);
+        assert_eq!(backoff.reserve(1050), 150);
+        // 空闲一段时间后不会积攒额度
+        assert_eq!(backoff.reserve(5000), 0);
+        assert_eq!(backoff.reserve(5000), 100);
+    }
+}
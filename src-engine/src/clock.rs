@@ -0,0 +1,134 @@
+// 根据上游响应的 Date 头估计服务器时钟与本地时钟的差
+// Date 头只精确到秒，每个样本只能确定差值所在的区间，多个样本取交集逐步缩小范围
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ServerClock {
+    // 服务器时间减本地时间的可能范围，单位毫秒
+    range: Option<(i64, i64)>,
+    samples: u32,
+}
+
+impl ServerClock {
+    // sent_ms 和 received_ms 为本地发出请求和收到响应的时间，返回估计是否有变化
+    pub fn record(&mut self, date_ms: u64, sent_ms: u64, received_ms: u64) -> bool {
+        // 服务器在发出和收到之间的某一时刻生成响应，那时服务器时间在 [date, date + 1000) 之间
+        let lower = date_ms as i64 - received_ms as i64;
+        let upper = date_ms as i64 + 999 - sent_ms as i64;
+        let range = match self.range {
+            Some((lo, hi)) if lo.max(lower) <= hi.min(upper) => (lo.max(lower), hi.min(upper)),
+            // 没有交集说明本地时钟被调整过，之前的样本作废
+            _ => (lower, upper),
+        };
+        self.samples += 1;
+        let changed = self.range != Some(range);
+        self.range = Some(range);
+        changed
+    }
+
+    // 服务器时间减本地时间，没有样本时为 None
+    pub fn offset_ms(&self) -> Option<i64> {
+        self.range.map(|(lo, hi)| lo + (hi - lo) / 2)
+    }
+
+    // 估计值的最大误差
+    pub fn uncertainty_ms(&self) -> Option<u64> {
+        self.range.map(|(lo, hi)| ((hi - lo) / 2) as u64)
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    // 本地时间对应的服务器时间，没有样本时认为两者一致
+    pub fn server_time(&self, local_ms: u64) -> u64 {
+        local_ms.saturating_add_signed(self.offset_ms().unwrap_or(0))
+    }
+
+    // 服务器时间对应的本地时间
+    pub fn local_time(&self, server_ms: u64) -> u64 {
+        server_ms.saturating_add_signed(-self.offset_ms().unwrap_or(0))
+    }
+}
+
+// 解析上游返回的北京时间，例如批次的 beginTime "2025-09-01 10:00:00"，返回 Unix 毫秒时间戳
+pub fn parse_upstream_time(text: &str) -> Option<u64> {
+    let (date, time) = text.trim().split_once(' ')?;
+    let mut date = date.split('-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    let mut time = time.split(':').map(str::parse::<i64>);
+    let (hour, minute) = (time.next()?.ok()?, time.next()?.ok()?);
+    let second = time.next().transpose().ok()?.unwrap_or(0);
+    if date.next().is_some()
+        || time.next().is_some()
+        || !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || !(0..24).contains(&hour)
+        || !(0..60).contains(&minute)
+        || !(0..60).contains(&second)
+    {
+        return None;
+    }
+
+    let seconds =
+        days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - 8 * 3600;
+    u64::try_from(seconds * 1000).ok()
+}
+
+// 公历日期距 1970-01-01 的天数
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_narrow_the_offset() {
+        let mut clock = ServerClock::default();
+        assert_eq!(clock.offset_ms(), None);
+        assert_eq!(clock.server_time(1000), 1000);
+
+        // 服务器比本地快 5.3 秒，请求往返 100ms
+        assert!(clock.record(5000, 0, 100));
+        assert_eq!(clock.offset_ms(), Some(4900 + (5999 - 4900) / 2));
+
+        // 刚过整秒时收到的响应确定下限，整秒前发出的请求确定上限
+        assert!(clock.record(6000, 600, 700));
+        assert!(clock.record(6000, 1600, 1700));
+        assert_eq!(clock.offset_ms(), Some(5349));
+        assert_eq!(clock.uncertainty_ms(), Some(49));
+        // 与已有范围一致的样本不改变估计
+        assert!(!clock.record(7000, 2000, 2100));
+        assert_eq!(clock.samples(), 4);
+        assert_eq!(clock.local_time(clock.server_time(42_000)), 42_000);
+    }
+
+    #[test]
+    fn inconsistent_sample_starts_over() {
+        let mut clock = ServerClock::default();
+        clock.record(5000, 0, 100);
+        // 本地时钟往回调了一分钟
+        clock.record(5000, 60_000, 60_100);
+        assert_eq!(clock.offset_ms(), Some(-55_100 + (-54_001 + 55_100) / 2));
+    }
+
+    #[test]
+    fn parses_beijing_time() {
+        assert_eq!(
+            parse_upstream_time("2025-09-01 10:00:00"),
+            Some(1_756_692_000_000)
+        );
+        assert_eq!(
+            parse_upstream_time("2024-02-29 08:00"),
+            Some(1_709_164_800_000)
+        );
+        assert_eq!(parse_upstream_time("2025-13-01 10:00:00"), None);
+        assert_eq!(parse_upstream_time("2025-09-01"), None);
+        assert_eq!(parse_upstream_time(""), None);
+    }
+}
@@ -0,0 +1,1607 @@
+use crate::backoff::{Backoff, BackoffState};
+use crate::clock::ServerClock;
+use crate::status::{Course, CourseStatus, classify};
+use crate::transport::{Reply, Timer, Transport, TransportError, Vacancy};
+use futures::channel::mpsc::{UnboundedReceiver, UnboundedSender, unbounded};
+use futures::channel::oneshot;
+use futures::future::{self, Either, FutureExt, Shared, join_all};
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::pin::pin;
+use std::rc::Rc;
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    // 同时进行的请求数
+    pub workers: usize,
+    // 每个请求结束后等待的时间，服务器正常时使用
+    pub interval_ms: u64,
+    // 服务器过载时等待时间的上限
+    pub max_interval_ms: u64,
+    // 等待时间上下浮动的百分比
+    pub jitter_percent: u32,
+    // 所有 worker 合计的每秒请求上限，None 为不限制
+    pub max_rps: Option<u32>,
+    // 定时开始前检查登录状态的间隔
+    pub keep_alive_ms: u64,
+    // 盯课模式下查询余量的间隔，只在有空位时发送选课请求，None 时直接发送选课请求
+    pub watch_ms: Option<u64>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            workers: 12,
+            interval_ms: 200,
+            max_interval_ms: 5000,
+            jitter_percent: 20,
+            max_rps: Some(30),
+            keep_alive_ms: 30_000,
+            watch_ms: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    // 调用了 stop
+    Stopped,
+    // 所有课程都已经有结果
+    Completed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    // 调用了 pause
+    User,
+    // 登录已失效，重新登录后调用 resume 继续
+    SessionExpired,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Phase {
+    #[default]
+    Idle,
+    // 等待服务器时间到达 at_ms 后开始
+    Scheduled {
+        at_ms: u64,
+    },
+    Running,
+    Paused(PauseReason),
+    Finished(StopReason),
+}
+
+// 单门课程的状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CourseState {
+    pub course: Course,
+    pub status: CourseStatus,
+    // 已发送的选课请求数，包括进行中的请求
+    pub attempts: u32,
+    // 还没有返回的选课请求数
+    pub in_flight: u32,
+    // 盯课模式下已发送的余量查询数和最近一次查询到的余量
+    pub checks: u32,
+    pub remaining: Option<u32>,
+    // 最近一次上游响应的 code 和 msg
+    pub last_code: Option<i64>,
+    pub last_msg: Option<String>,
+    // 最近一次请求的耗时
+    pub latency_ms: Option<u64>,
+    // 第一次选课成功的时间，Unix 毫秒时间戳
+    pub selected_at: Option<u64>,
+    // 最近一次没有得到上游响应的原因
+    pub last_error: Option<TransportError>,
+}
+
+impl CourseState {
+    fn new(course: Course) -> Self {
+        Self {
+            course,
+            status: CourseStatus::Waiting,
+            attempts: 0,
+            in_flight: 0,
+            checks: 0,
+            remaining: None,
+            last_code: None,
+            last_msg: None,
+            latency_ms: None,
+            selected_at: None,
+            last_error: None,
+        }
+    }
+
+    // 按这门课程的策略判断是否已经有结果
+    fn is_final(&self) -> bool {
+        self.status.is_final(self.course.policy.retry_when_full)
+    }
+
+    // 达到请求次数上限或放弃时间，不再发送新的请求
+    fn is_exhausted(&self, server_ms: u64) -> bool {
+        let policy = self.course.policy;
+        policy.max_attempts.is_some_and(|max| self.attempts >= max)
+            || policy.give_up_at_ms.is_some_and(|at| server_ms >= at)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    pub phase: Phase,
+    // 还没有退出的 worker 数，停止后降为 0 时所有请求都已结束
+    pub workers: usize,
+    pub total_requests: u64,
+    pub backoff: BackoffState,
+    pub clock: ServerClock,
+    pub courses: Vec<CourseState>,
+}
+
+// 引擎推送的事件，index 为课程在列表中的位置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Phase(Phase),
+    Request {
+        index: usize,
+    },
+    Course {
+        index: usize,
+        state: Box<CourseState>,
+    },
+    Backoff(BackoffState),
+    Workers(usize),
+    Clock(ServerClock),
+}
+
+// 引擎结束时完成，用来打断进行中的请求和等待
+type Stopped = Shared<oneshot::Receiver<()>>;
+
+struct Inner {
+    options: Options,
+    backoff: RefCell<Backoff>,
+    state: RefCell<Snapshot>,
+    subscribers: RefCell<Vec<UnboundedSender<Event>>>,
+    // 丢弃后 Stopped 完成
+    stop_signal: RefCell<Option<oneshot::Sender<()>>>,
+    // 还在等待定时开始时为开始的服务器时间
+    scheduled_at: Cell<Option<u64>>,
+}
+
+// 选课引擎的句柄，克隆后指向同一个引擎
+// start 返回的 Future 由调用方驱动：WebView 中交给 spawn_local，原生环境中交给 tokio
+#[derive(Clone)]
+pub struct Engine(Rc<Inner>);
+
+impl Engine {
+    pub fn new(courses: Vec<Course>, options: Options) -> Self {
+        let courses = courses
+            .into_iter()
+            .map(|mut course| {
+                // 盯课就是在满员时等待空位，抢输了也继续盯
+                if options.watch_ms.is_some() {
+                    course.policy.retry_when_full = true;
+                }
+                CourseState::new(course)
+            })
+            .collect();
+        let backoff = Backoff::new(&options, 0);
+        Self(Rc::new(Inner {
+            state: RefCell::new(Snapshot {
+                phase: Phase::Idle,
+                workers: 0,
+                total_requests: 0,
+                backoff: backoff.state(),
+                clock: ServerClock::default(),
+                courses,
+            }),
+            backoff: RefCell::new(backoff),
+            options,
+            subscribers: RefCell::new(Vec::new()),
+            stop_signal: RefCell::new(None),
+            scheduled_at: Cell::new(None),
+        }))
+    }
+
+    // 订阅之后的事件，接收端被丢弃后自动取消订阅
+    pub fn subscribe(&self) -> UnboundedReceiver<Event> {
+        let (tx, rx) = unbounded();
+        self.0.subscribers.borrow_mut().push(tx);
+        rx
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.0.state.borrow().clone()
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.0.state.borrow().phase
+    }
+
+    // 已经开始且还有 worker 在运行，包括等待定时、暂停和正在停止的引擎
+    pub fn is_active(&self) -> bool {
+        let state = self.0.state.borrow();
+        matches!(
+            state.phase,
+            Phase::Scheduled { .. } | Phase::Running | Phase::Paused(_)
+        ) || state.workers > 0
+    }
+
+    // 暂停后进行中的请求会正常完成，之后不再发送新请求
+    pub fn pause(&self) {
+        self.pause_for(PauseReason::User);
+    }
+
+    // 课程队列和计数保持不变，从暂停的位置继续，还没到定时开始的时间时继续等待
+    pub fn resume(&self) {
+        if let Phase::Paused(_) = self.phase() {
+            self.set_phase(match self.0.scheduled_at.get() {
+                Some(at_ms) => Phase::Scheduled { at_ms },
+                None => Phase::Running,
+            });
+        }
+    }
+
+    // 进行中的请求会被取消，所有 worker 退出后 start 返回
+    pub fn stop(&self) {
+        self.finish(StopReason::Stopped);
+    }
+
+    // 开始选课，直到所有课程都有结果或调用 stop 时返回
+    // 登录失效时自动暂停，等待重新登录后 resume
+    // 每个引擎只能开始一次，重复调用不会启动新的 worker
+    pub async fn start(&self, transport: impl Transport, timer: impl Timer) -> StopReason {
+        self.run(transport, timer, None).await
+    }
+
+    // 服务器时间到达 at_ms 时开始选课，之前只定期检查登录状态并校准服务器时钟
+    pub async fn start_at(
+        &self,
+        transport: impl Transport,
+        timer: impl Timer,
+        at_ms: u64,
+    ) -> StopReason {
+        self.run(transport, timer, Some(at_ms)).await
+    }
+
+    async fn run(
+        &self,
+        transport: impl Transport,
+        timer: impl Timer,
+        at_ms: Option<u64>,
+    ) -> StopReason {
+        if self.phase() != Phase::Idle {
+            log::warn!("Engine already started");
+        } else if self.0.state.borrow().courses.is_empty() {
+            self.finish(StopReason::Completed);
+        } else {
+            let (tx, rx) = oneshot::channel();
+            *self.0.stop_signal.borrow_mut() = Some(tx);
+            let stopped = rx.shared();
+
+            if let Some(at_ms) = at_ms {
+                self.0.scheduled_at.set(Some(at_ms));
+                self.set_phase(Phase::Scheduled { at_ms });
+                self.wait_until(at_ms, &transport, &timer, &stopped).await;
+                self.0.scheduled_at.set(None);
+                if matches!(self.phase(), Phase::Finished(_)) {
+                    return StopReason::Stopped;
+                }
+            }
+
+            self.0.backoff.borrow_mut().seed(timer.now_ms());
+            // 盯课模式的请求频率很低，一个 worker 轮流查询所有课程
+            let count = match self.0.options.watch_ms {
+                Some(_) => 1,
+                None => self.0.options.workers.max(1),
+            };
+            self.0.state.borrow_mut().workers = count;
+            self.emit(Event::Workers(count));
+            // 等待期间登录失效时保持暂停，重新登录后再开始请求
+            if !matches!(self.phase(), Phase::Paused(_)) {
+                self.set_phase(Phase::Running);
+            }
+
+            let (transport, timer) = (&transport, &timer);
+            let workers = (0..count).map(|id| {
+                let stopped = stopped.clone();
+                let guard = WorkerGuard(self);
+                async move {
+                    self.worker(id, transport, timer, &stopped).await;
+                    drop(guard);
+                }
+            });
+            join_all(workers).await;
+        }
+
+        match self.phase() {
+            Phase::Finished(reason) => reason,
+            _ => StopReason::Stopped,
+        }
+    }
+
+    async fn worker(
+        &self,
+        id: usize,
+        transport: &impl Transport,
+        timer: &impl Timer,
+        stopped: &Stopped,
+    ) {
+        let interval = self.0.options.interval_ms;
+        let mut next = id;
+
+        loop {
+            match self.phase() {
+                Phase::Finished(_) => break,
+                Phase::Paused(_) => {
+                    if unless_stopped(stopped, timer.sleep(interval))
+                        .await
+                        .is_none()
+                    {
+                        break;
+                    }
+                    continue;
+                }
+                Phase::Idle | Phase::Scheduled { .. } | Phase::Running => {}
+            }
+
+            // 超过每秒请求上限时等待，等待期间可能已经暂停或停止
+            let wait = self.0.backoff.borrow_mut().reserve(timer.now_ms());
+            if wait > 0 {
+                if unless_stopped(stopped, timer.sleep(wait)).await.is_none() {
+                    break;
+                }
+                if self.phase() != Phase::Running {
+                    continue;
+                }
+            }
+
+            // 已有结果的课程退出轮询，全部有结果时结束
+            // 其他 worker 还有请求没有返回时先退出，由最后得到响应的 worker 结束
+            let server_ms = self.server_time(timer.now_ms());
+            self.give_up_exhausted(server_ms);
+            let Some(index) = self.next_pending(next, server_ms) else {
+                if self.in_flight() == 0 {
+                    self.finish(StopReason::Completed);
+                }
+                break;
+            };
+            next = index + 1;
+
+            // 盯课模式下先查询余量，没有空位时等待下一次查询
+            if let Some(watch_ms) = self.0.options.watch_ms {
+                match self.check_vacancy(index, transport, timer, stopped).await {
+                    None => break,
+                    Some(true) if self.phase() == Phase::Running => {}
+                    Some(_) => {
+                        let delay = self.0.backoff.borrow_mut().delay().max(watch_ms);
+                        if unless_stopped(stopped, timer.sleep(delay)).await.is_none() {
+                            break;
+                        }
+                        continue;
+                    }
+                }
+            }
+
+            let course = {
+                let mut state = self.0.state.borrow_mut();
+                state.total_requests += 1;
+                state.courses[index].attempts += 1;
+                state.courses[index].in_flight += 1;
+                state.courses[index].course.clone()
+            };
+            self.emit(Event::Request { index });
+
+            // 停止时取消进行中的请求，之后返回的结果不再记录
+            let sent_ms = timer.now_ms();
+            let result = unless_stopped(stopped, transport.select(&course)).await;
+            self.0.state.borrow_mut().courses[index].in_flight -= 1;
+            let Some(result) = result else {
+                break;
+            };
+            if matches!(self.phase(), Phase::Finished(_)) {
+                break;
+            }
+            self.record_date(&result, sent_ms, timer.now_ms());
+
+            self.record_backoff(&result);
+
+            let status = match &result {
+                Ok(reply) => classify(reply),
+                Err(e) => {
+                    log::error!("请求错误: {e:?}");
+                    CourseStatus::Error(e.clone())
+                }
+            };
+            let not_logged_in = status == CourseStatus::NotLoggedIn;
+
+            let state = {
+                let mut state = self.0.state.borrow_mut();
+                let course = &mut state.courses[index];
+                match result {
+                    Ok(reply) => {
+                        course.last_code = Some(reply.code);
+                        course.last_msg = Some(reply.msg);
+                        course.latency_ms = reply.latency_ms;
+                    }
+                    Err(e) => {
+                        course.latency_ms = None;
+                        course.last_error = Some(e);
+                    }
+                }
+                if status == CourseStatus::Selected && course.selected_at.is_none() {
+                    course.selected_at = Some(timer.now_ms());
+                }
+                // 其他 worker 已经得到结果时保留先到的结果，但同组已选后晚到的成功仍然记录
+                if status == CourseStatus::Selected || !course.is_final() {
+                    course.status = status;
+                }
+                course.clone()
+            };
+            let group = state.course.group;
+            let enrolled = matches!(
+                state.status,
+                CourseStatus::Selected | CourseStatus::AlreadySelected
+            );
+            self.emit(Event::Course {
+                index,
+                state: Box::new(state),
+            });
+            if enrolled && let Some(group) = group {
+                self.skip_group(group);
+            }
+
+            // 登录失效时所有课程都无法继续，暂停等待重新登录
+            if not_logged_in {
+                self.pause_for(PauseReason::SessionExpired);
+                continue;
+            }
+
+            // 短暂延迟避免请求过快，服务器过载时延长
+            let delay = self.0.backoff.borrow_mut().delay();
+            if unless_stopped(stopped, timer.sleep(delay)).await.is_none() {
+                break;
+            }
+        }
+    }
+
+    // 查询一门课程的余量，有空位时返回 true，引擎结束时返回 None
+    async fn check_vacancy(
+        &self,
+        index: usize,
+        transport: &impl Transport,
+        timer: &impl Timer,
+        stopped: &Stopped,
+    ) -> Option<bool> {
+        let course = {
+            let mut state = self.0.state.borrow_mut();
+            state.total_requests += 1;
+            state.courses[index].checks += 1;
+            state.courses[index].course.clone()
+        };
+
+        let sent_ms = timer.now_ms();
+        let result = unless_stopped(stopped, transport.vacancy(&course)).await?;
+        if matches!(self.phase(), Phase::Finished(_)) {
+            return None;
+        }
+        let (result, remaining) = match result {
+            Ok(Vacancy { reply, remaining }) => (Ok(reply), remaining),
+            Err(e) => (Err(e), None),
+        };
+        self.record_date(&result, sent_ms, timer.now_ms());
+        self.record_backoff(&result);
+
+        let not_logged_in = result
+            .as_ref()
+            .is_ok_and(|reply| classify(reply) == CourseStatus::NotLoggedIn);
+        let state = {
+            let mut state = self.0.state.borrow_mut();
+            let course = &mut state.courses[index];
+            match result {
+                Ok(reply) => {
+                    course.last_code = Some(reply.code);
+                    course.last_msg = Some(reply.msg);
+                    course.latency_ms = reply.latency_ms;
+                }
+                Err(e) => {
+                    log::warn!("Vacancy check failed: {e:?}");
+                    course.latency_ms = None;
+                    course.last_error = Some(e);
+                }
+            }
+            if !course.is_final() {
+                if not_logged_in {
+                    course.status = CourseStatus::NotLoggedIn;
+                } else if remaining == Some(0) {
+                    course.status = CourseStatus::Full;
+                }
+            }
+            if remaining.is_some() {
+                course.remaining = remaining;
+            }
+            course.clone()
+        };
+        self.emit(Event::Course {
+            index,
+            state: Box::new(state),
+        });
+
+        if not_logged_in {
+            self.pause_for(PauseReason::SessionExpired);
+        }
+        Some(remaining.is_some_and(|remaining| remaining > 0))
+    }
+
+    // 等到服务器时间到达 at_ms，期间定期发送 keep_alive 确认登录状态，同时校准服务器时钟
+    async fn wait_until(
+        &self,
+        at_ms: u64,
+        transport: &impl Transport,
+        timer: &impl Timer,
+        stopped: &Stopped,
+    ) {
+        let keep_alive_ms = self.0.options.keep_alive_ms.max(1000);
+        // 开始等待时立即检查一次
+        let mut next_check = timer.now_ms();
+
+        loop {
+            let now = timer.now_ms();
+            let remaining = at_ms.saturating_sub(self.server_time(now));
+            if remaining == 0 {
+                return;
+            }
+
+            // 登录失效暂停时不再检查，等待重新登录
+            let scheduled = matches!(self.phase(), Phase::Scheduled { .. });
+            if scheduled && now >= next_check {
+                next_check = now + keep_alive_ms;
+                let Some(result) = unless_stopped(stopped, transport.keep_alive()).await else {
+                    return;
+                };
+                self.record_date(&result, now, timer.now_ms());
+                match result {
+                    Ok(reply) if classify(&reply) == CourseStatus::NotLoggedIn => {
+                        self.pause_for(PauseReason::SessionExpired);
+                    }
+                    Ok(_) => {}
+                    Err(e) => log::warn!("Keep-alive failed: {e:?}"),
+                }
+                continue;
+            }
+
+            // 每秒醒来一次，校准结果变化后及时调整开始时间
+            let mut wait = remaining.min(1000);
+            if scheduled {
+                wait = wait.min(next_check - now);
+            }
+            if unless_stopped(stopped, timer.sleep(wait)).await.is_none() {
+                return;
+            }
+        }
+    }
+
+    fn server_time(&self, local_ms: u64) -> u64 {
+        self.0.state.borrow().clock.server_time(local_ms)
+    }
+
+    // 用响应的 Date 头校准服务器时钟
+    fn record_date(&self, result: &Result<Reply, TransportError>, sent_ms: u64, received_ms: u64) {
+        let Ok(Reply {
+            date_ms: Some(date_ms),
+            ..
+        }) = result
+        else {
+            return;
+        };
+        let clock = {
+            let mut state = self.0.state.borrow_mut();
+            if !state.clock.record(*date_ms, sent_ms, received_ms) {
+                return;
+            }
+            state.clock
+        };
+        self.emit(Event::Clock(clock));
+    }
+
+    // 服务器过载时延长请求间隔
+    fn record_backoff(&self, result: &Result<Reply, TransportError>) {
+        if self.0.backoff.borrow_mut().record(result) {
+            let backoff = self.0.backoff.borrow().state();
+            self.0.state.borrow_mut().backoff = backoff;
+            self.emit(Event::Backoff(backoff));
+        }
+    }
+
+    // 同组已经选上一门，其余还没有结果的课程不再请求
+    fn skip_group(&self, group: usize) {
+        self.settle(CourseStatus::Skipped, |c| c.course.group == Some(group));
+    }
+
+    // 达到请求次数上限或放弃时间的课程等所有请求返回后标记放弃
+    // 最后一个请求选上时记录为已选，不会被放弃覆盖
+    fn give_up_exhausted(&self, server_ms: u64) {
+        self.settle(CourseStatus::GaveUp, |c| {
+            c.in_flight == 0 && c.is_exhausted(server_ms)
+        });
+    }
+
+    // 把满足条件且还没有结果的课程设为 status
+    fn settle(&self, status: CourseStatus, matches: impl Fn(&CourseState) -> bool) {
+        let settled: Vec<(usize, CourseState)> = {
+            let mut state = self.0.state.borrow_mut();
+            state
+                .courses
+                .iter_mut()
+                .enumerate()
+                .filter(|(_, c)| !c.is_final() && matches(c))
+                .map(|(index, c)| {
+                    c.status = status.clone();
+                    (index, c.clone())
+                })
+                .collect()
+        };
+        for (index, state) in settled {
+            self.emit(Event::Course {
+                index,
+                state: Box::new(state),
+            });
+        }
+    }
+
+    // 从 from 开始轮询查找下一门还没有结果且可以继续请求的课程
+    fn next_pending(&self, from: usize, server_ms: u64) -> Option<usize> {
+        let state = self.0.state.borrow();
+        let count = state.courses.len();
+        (0..count).map(|offset| (from + offset) % count).find(|&i| {
+            let course = &state.courses[i];
+            !course.is_final() && !course.is_exhausted(server_ms)
+        })
+    }
+
+    // 所有课程还没有返回的选课请求数
+    fn in_flight(&self) -> u32 {
+        self.0
+            .state
+            .borrow()
+            .courses
+            .iter()
+            .map(|c| c.in_flight)
+            .sum()
+    }
+
+    fn pause_for(&self, reason: PauseReason) {
+        if matches!(self.phase(), Phase::Scheduled { .. } | Phase::Running) {
+            self.set_phase(Phase::Paused(reason));
+        }
+    }
+
+    fn finish(&self, reason: StopReason) {
+        if !matches!(self.phase(), Phase::Finished(_)) {
+            self.set_phase(Phase::Finished(reason));
+            self.0.stop_signal.borrow_mut().take();
+        }
+    }
+
+    fn set_phase(&self, phase: Phase) {
+        self.0.state.borrow_mut().phase = phase;
+        self.emit(Event::Phase(phase));
+    }
+
+    fn emit(&self, event: Event) {
+        self.0
+            .subscribers
+            .borrow_mut()
+            .retain(|tx| tx.unbounded_send(event.clone()).is_ok());
+    }
+}
+
+// worker 退出时减少计数，start 的 Future 被丢弃时同样生效
+struct WorkerGuard<'a>(&'a Engine);
+
+impl Drop for WorkerGuard<'_> {
+    fn drop(&mut self) {
+        let workers = {
+            let mut state = self.0.0.state.borrow_mut();
+            state.workers -= 1;
+            state.workers
+        };
+        self.0.emit(Event::Workers(workers));
+    }
+}
+
+// 等待 future 完成，引擎先结束时丢弃它并返回 None
+// 传输层在请求的 Future 被丢弃时取消对应的网络请求
+async fn unless_stopped<F: Future>(stopped: &Stopped, future: F) -> Option<F::Output> {
+    match future::select(pin!(future), stopped.clone()).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status::CoursePolicy;
+    use crate::transport::{Reply, Vacancy};
+    use futures::StreamExt;
+    use std::cell::Cell;
+    use std::collections::{HashMap, VecDeque};
+
+    // 按课程预设响应，用完后一直返回“未开始”
+    #[derive(Default)]
+    struct FakeTransport {
+        replies: RefCell<HashMap<String, VecDeque<Result<Reply, TransportError>>>>,
+        calls: Cell<u32>,
+        // 得到响应的请求数，被取消的请求不计入
+        answered: Cell<u32>,
+        // 每个请求的耗时，默认 50ms
+        latency_ms: Option<u64>,
+        // 按课程单独设置的请求耗时
+        course_latency: HashMap<String, u64>,
+        // keep_alive 的预设响应，用完后返回带 Date 头的 200
+        keep_alive: RefCell<VecDeque<Reply>>,
+        keep_alive_calls: Cell<u32>,
+        // 设置后 keep_alive 的响应带上服务器时间，服务器比测试开始时的 0 快 server_ahead_ms
+        server_clock: Option<(tokio::time::Instant, u64)>,
+        // 按课程预设的余量查询结果，用完后一直返回没有空位
+        vacancies: RefCell<HashMap<String, VecDeque<Result<Vacancy, TransportError>>>>,
+        vacancy_calls: Cell<u32>,
+    }
+
+    impl FakeTransport {
+        fn latency(self, ms: u64) -> Self {
+            Self {
+                latency_ms: Some(ms),
+                ..self
+            }
+        }
+
+        fn slow(mut self, id: &str, ms: u64) -> Self {
+            self.course_latency.insert(id.to_string(), ms);
+            self
+        }
+
+        fn server_ahead(self, ms: u64) -> Self {
+            Self {
+                server_clock: Some((tokio::time::Instant::now(), ms)),
+                ..self
+            }
+        }
+
+        fn script_keep_alive(self, replies: Vec<Reply>) -> Self {
+            *self.keep_alive.borrow_mut() = replies.into();
+            self
+        }
+
+        fn script_vacancy(self, id: &str, remaining: Vec<u32>) -> Self {
+            let vacancies = remaining
+                .into_iter()
+                .map(|remaining| {
+                    Ok(Vacancy {
+                        reply: reply(200, "查询成功"),
+                        remaining: Some(remaining),
+                    })
+                })
+                .collect();
+            self.vacancies
+                .borrow_mut()
+                .insert(id.to_string(), vacancies);
+            self
+        }
+
+        fn script(self, id: &str, replies: Vec<Result<Reply, TransportError>>) -> Self {
+            self.replies
+                .borrow_mut()
+                .insert(id.to_string(), replies.into());
+            self
+        }
+    }
+
+    impl Transport for &FakeTransport {
+        async fn select(&self, course: &Course) -> Result<Reply, TransportError> {
+            self.calls.set(self.calls.get() + 1);
+            let latency = self
+                .course_latency
+                .get(&course.id)
+                .copied()
+                .or(self.latency_ms)
+                .unwrap_or(50);
+            tokio::time::sleep(std::time::Duration::from_millis(latency)).await;
+            self.answered.set(self.answered.get() + 1);
+            self.replies
+                .borrow_mut()
+                .get_mut(&course.id)
+                .and_then(VecDeque::pop_front)
+                .unwrap_or_else(|| Ok(reply(500, "本轮次选课暂未开始")))
+        }
+
+        async fn keep_alive(&self) -> Result<Reply, TransportError> {
+            self.keep_alive_calls.set(self.keep_alive_calls.get() + 1);
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            let scripted = self.keep_alive.borrow_mut().pop_front();
+            Ok(scripted.unwrap_or_else(|| Reply {
+                // Date 头只精确到秒
+                date_ms: self.server_clock.map(|(origin, ahead)| {
+                    (origin.elapsed().as_millis() as u64 + ahead) / 1000 * 1000
+                }),
+                ..reply(200, "查询成功")
+            }))
+        }
+
+        async fn vacancy(&self, course: &Course) -> Result<Vacancy, TransportError> {
+            self.vacancy_calls.set(self.vacancy_calls.get() + 1);
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            self.vacancies
+                .borrow_mut()
+                .get_mut(&course.id)
+                .and_then(VecDeque::pop_front)
+                .unwrap_or_else(|| {
+                    Ok(Vacancy {
+                        reply: reply(200, "查询成功"),
+                        remaining: Some(0),
+                    })
+                })
+        }
+    }
+
+    struct TestTimer(tokio::time::Instant);
+
+    impl TestTimer {
+        fn new() -> Self {
+            Self(tokio::time::Instant::now())
+        }
+    }
+
+    impl Timer for TestTimer {
+        async fn sleep(&self, ms: u64) {
+            tokio::time::sleep(std::time::Duration::from_millis(ms)).await;
+        }
+
+        // 以测试开始时为 0 的虚拟时间
+        fn now_ms(&self) -> u64 {
+            self.0.elapsed().as_millis() as u64
+        }
+    }
+
+    fn reply(code: i64, msg: &str) -> Reply {
+        Reply {
+            code,
+            msg: msg.to_string(),
+            latency_ms: Some(50),
+            date_ms: None,
+        }
+    }
+
+    fn course(id: &str) -> Course {
+        Course {
+            id: id.to_string(),
+            name: format!("课程{id}"),
+            class_type: "TJKC".to_string(),
+            secret_val: String::new(),
+            group: None,
+            policy: CoursePolicy::default(),
+        }
+    }
+
+    fn options() -> Options {
+        Options {
+            workers: 2,
+            interval_ms: 100,
+            max_interval_ms: 1000,
+            jitter_percent: 0,
+            max_rps: None,
+            keep_alive_ms: 30_000,
+            watch_ms: None,
+        }
+    }
+
+    fn watching() -> Options {
+        Options {
+            watch_ms: Some(10_000),
+            ..options()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn completes_when_every_course_is_final() {
+        let transport = FakeTransport::default()
+            .script(
+                "a",
+                vec![
+                    Ok(reply(500, "本轮次选课暂未开始")),
+                    Ok(reply(200, "选课成功")),
+                ],
+            )
+            .script("b", vec![Ok(reply(500, "该课程已在选课结果中"))]);
+        let options = Options {
+            workers: 1,
+            ..options()
+        };
+        let engine = Engine::new(vec![course("a"), course("b")], options);
+
+        let reason = engine.start(&transport, TestTimer::new()).await;
+
+        assert_eq!(reason, StopReason::Completed);
+        let snapshot = engine.snapshot();
+        assert_eq!(snapshot.phase, Phase::Finished(StopReason::Completed));
+        assert_eq!(snapshot.courses[0].status, CourseStatus::Selected);
+        assert_eq!(snapshot.courses[0].attempts, 2);
+        assert_eq!(snapshot.courses[0].latency_ms, Some(50));
+        assert_eq!(snapshot.courses[0].last_code, Some(200));
+        assert_eq!(snapshot.courses[0].last_msg.as_deref(), Some("选课成功"));
+        // 依次请求 a、b、a，每次请求 50ms，间隔 100ms
+        assert_eq!(snapshot.courses[0].selected_at, Some(350));
+        assert_eq!(snapshot.courses[1].status, CourseStatus::AlreadySelected);
+        assert_eq!(snapshot.courses[1].selected_at, None);
+        assert_eq!(snapshot.courses[1].attempts, 1);
+        assert_eq!(snapshot.total_requests, transport.calls.get() as u64);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn selected_course_leaves_the_rotation() {
+        let transport = FakeTransport::default().script("a", vec![Ok(reply(200, "选课成功"))]);
+        let engine = Engine::new(vec![course("a"), course("b")], options());
+
+        let run = engine.start(&transport, TestTimer::new());
+        let control = async {
+            tokio::time::sleep(std::time::Duration::from_millis(2000)).await;
+            engine.stop();
+        };
+        let (reason, ()) = futures::join!(run, control);
+
+        assert_eq!(reason, StopReason::Stopped);
+        let snapshot = engine.snapshot();
+        // b 一直未开始，a 成功后不再发送请求
+        assert_eq!(snapshot.courses[0].status, CourseStatus::Selected);
+        assert!(snapshot.courses[0].attempts <= 2);
+        assert_eq!(snapshot.courses[1].status, CourseStatus::NotStarted);
+        assert!(snapshot.courses[1].attempts > 10);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn late_replies_do_not_overwrite_a_result() {
+        let transport = FakeTransport::default().script(
+            "a",
+            vec![
+                Ok(reply(200, "选课成功")),
+                Ok(reply(500, "该课程已在选课结果中")),
+            ],
+        );
+        // 两个 worker 同时请求 a，第二个响应晚到
+        let engine = Engine::new(vec![course("a")], options());
+
+        assert_eq!(
+            engine.start(&transport, TestTimer::new()).await,
+            StopReason::Completed
+        );
+        assert_eq!(engine.snapshot().courses[0].status, CourseStatus::Selected);
+        assert_eq!(engine.snapshot().courses[0].attempts, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pauses_when_the_session_expires_and_resumes() {
+        let transport = FakeTransport::default().script(
+            "a",
+            vec![
+                Ok(reply(500, "本轮次选课暂未开始")),
+                Ok(reply(401, "请先登录")),
+                Ok(reply(200, "选课成功")),
+            ],
+        );
+        let options = Options {
+            workers: 1,
+            ..options()
+        };
+        let engine = Engine::new(vec![course("a")], options);
+
+        let run = engine.start(&transport, TestTimer::new());
+        let control = async {
+            tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
+            assert_eq!(engine.phase(), Phase::Paused(PauseReason::SessionExpired));
+            let snapshot = engine.snapshot();
+            assert_eq!(snapshot.courses[0].status, CourseStatus::NotLoggedIn);
+            assert_eq!(snapshot.total_requests, 2);
+            assert_eq!(transport.calls.get(), 2);
+
+            // 重新登录后继续，计数保留
+            engine.resume();
+        };
+        let (reason, ()) = futures::join!(run, control);
+
+        assert_eq!(reason, StopReason::Completed);
+        let snapshot = engine.snapshot();
+        assert_eq!(snapshot.courses[0].status, CourseStatus::Selected);
+        assert_eq!(snapshot.courses[0].attempts, 3);
+        assert_eq!(snapshot.total_requests, 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stop_cancels_requests_in_flight() {
+        let transport = FakeTransport::default().latency(10_000);
+        let engine = Engine::new(vec![course("a"), course("b")], options());
+        let mut events = engine.subscribe();
+        let started = tokio::time::Instant::now();
+
+        let run = engine.start(&transport, TestTimer::new());
+        let control = async {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            engine.stop();
+        };
+        let (reason, ()) = futures::join!(run, control);
+
+        // 不等上游响应，所有 worker 立即退出
+        assert_eq!(reason, StopReason::Stopped);
+        assert!(started.elapsed() < std::time::Duration::from_millis(200));
+        assert_eq!(transport.calls.get(), 2);
+        assert_eq!(transport.answered.get(), 0);
+        let snapshot = engine.snapshot();
+        assert_eq!(snapshot.workers, 0);
+        assert!(!engine.is_active());
+        assert!(
+            snapshot
+                .courses
+                .iter()
+                .all(|c| c.status == CourseStatus::Waiting && c.attempts == 1)
+        );
+
+        drop(engine);
+        let mut workers = Vec::new();
+        while let Some(event) = events.next().await {
+            if let Event::Workers(count) = event {
+                workers.push(count);
+            }
+        }
+        assert_eq!(workers, vec![2, 1, 0]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn starting_twice_does_not_add_workers() {
+        let transport = FakeTransport::default();
+        let options = Options {
+            workers: 1,
+            ..options()
+        };
+        let engine = Engine::new(vec![course("a")], options);
+
+        let run = engine.start(&transport, TestTimer::new());
+        let control = async {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            assert!(engine.is_active());
+            // 第二次 start 立即返回，不影响正在运行的 worker
+            engine.start(&transport, TestTimer::new()).await;
+            assert_eq!(engine.snapshot().workers, 1);
+            tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
+            engine.stop();
+        };
+        futures::join!(run, control);
+
+        // 一个 worker 每 150ms 发送一个请求
+        assert!(transport.calls.get() <= 7, "{}", transport.calls.get());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn scheduled_start_waits_for_the_server_clock() {
+        let transport = FakeTransport::default().server_ahead(5300);
+        let engine = Engine::new(vec![course("a")], options());
+
+        let run = engine.start_at(&transport, TestTimer::new(), 10_000);
+        let control = async {
+            tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
+            assert_eq!(engine.phase(), Phase::Scheduled { at_ms: 10_000 });
+            assert!(engine.is_active());
+            // 0ms 发出，50ms 收到 Date 为 5000 的响应，偏差在 [4950, 5999] 之间
+            let clock = engine.snapshot().clock;
+            assert_eq!(clock.offset_ms(), Some(5474));
+            assert_eq!(transport.keep_alive_calls.get(), 1);
+
+            // 按估计的偏差在本地 4526ms 时开始
+            tokio::time::sleep(std::time::Duration::from_millis(3500)).await;
+            assert_eq!(transport.calls.get(), 0);
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            assert_eq!(engine.phase(), Phase::Running);
+            assert!(transport.calls.get() > 0);
+            engine.stop();
+        };
+        let (reason, ()) = futures::join!(run, control);
+
+        assert_eq!(reason, StopReason::Stopped);
+        assert_eq!(transport.keep_alive_calls.get(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn session_expiry_while_scheduled_keeps_waiting_after_resume() {
+        let transport = FakeTransport::default().script_keep_alive(vec![reply(401, "请先登录")]);
+        let engine = Engine::new(vec![course("a")], options());
+
+        let run = engine.start_at(&transport, TestTimer::new(), 3000);
+        let control = async {
+            tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
+            assert_eq!(engine.phase(), Phase::Paused(PauseReason::SessionExpired));
+
+            engine.resume();
+            assert_eq!(engine.phase(), Phase::Scheduled { at_ms: 3000 });
+            tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+            assert_eq!(transport.calls.get(), 0);
+            tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
+            assert!(transport.calls.get() > 0);
+            engine.stop();
+        };
+        let (reason, ()) = futures::join!(run, control);
+
+        assert_eq!(reason, StopReason::Stopped);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stop_while_scheduled_never_sends_requests() {
+        let transport = FakeTransport::default();
+        let engine = Engine::new(vec![course("a")], options());
+
+        let run = engine.start_at(&transport, TestTimer::new(), 60_000);
+        let control = async {
+            tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
+            engine.stop();
+        };
+        let (reason, ()) = futures::join!(run, control);
+
+        assert_eq!(reason, StopReason::Stopped);
+        assert_eq!(transport.calls.get(), 0);
+        assert!(!engine.is_active());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn one_section_per_group_is_enough() {
+        let transport = FakeTransport::default()
+            .script("b", vec![Ok(reply(200, "选课成功"))])
+            .script("c", vec![Ok(reply(200, "选课成功"))]);
+        let grouped = |id| Course {
+            group: Some(0),
+            ..course(id)
+        };
+        let options = Options {
+            workers: 1,
+            ..options()
+        };
+        // a、b 为同一门课的两个教学班，c 单独选
+        let engine = Engine::new(vec![grouped("a"), grouped("b"), course("c")], options);
+
+        assert_eq!(
+            engine.start(&transport, TestTimer::new()).await,
+            StopReason::Completed
+        );
+        let snapshot = engine.snapshot();
+        assert_eq!(snapshot.courses[0].status, CourseStatus::Skipped);
+        assert_eq!(snapshot.courses[0].attempts, 1);
+        assert_eq!(snapshot.courses[1].status, CourseStatus::Selected);
+        assert_eq!(snapshot.courses[2].status, CourseStatus::Selected);
+        assert_eq!(snapshot.total_requests, 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn full_course_stops_without_retry() {
+        let transport = FakeTransport::default().script("a", vec![Ok(reply(500, "课容量已满"))]);
+        let mut course = course("a");
+        course.policy.retry_when_full = false;
+        let engine = Engine::new(vec![course], options());
+
+        assert_eq!(
+            engine.start(&transport, TestTimer::new()).await,
+            StopReason::Completed
+        );
+        assert_eq!(engine.snapshot().courses[0].status, CourseStatus::Full);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn full_course_is_retried_when_enabled() {
+        let transport = FakeTransport::default().script(
+            "a",
+            vec![
+                Ok(reply(500, "课容量已满")),
+                Ok(reply(500, "课容量已满")),
+                Ok(reply(200, "选课成功")),
+            ],
+        );
+        // 只用一个 worker，保证请求按顺序发送
+        let options = Options {
+            workers: 1,
+            ..options()
+        };
+        let engine = Engine::new(vec![course("a")], options);
+
+        assert_eq!(
+            engine.start(&transport, TestTimer::new()).await,
+            StopReason::Completed
+        );
+        assert_eq!(engine.snapshot().courses[0].attempts, 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_after_max_attempts() {
+        let transport = FakeTransport::default();
+        let mut limited = course("a");
+        limited.policy.max_attempts = Some(3);
+        let options = Options {
+            workers: 1,
+            ..options()
+        };
+        let engine = Engine::new(vec![limited], options);
+
+        assert_eq!(
+            engine.start(&transport, TestTimer::new()).await,
+            StopReason::Completed
+        );
+        let snapshot = engine.snapshot();
+        assert_eq!(snapshot.courses[0].status, CourseStatus::GaveUp);
+        assert_eq!(snapshot.courses[0].attempts, 3);
+        assert_eq!(transport.calls.get(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn attempt_limit_waits_for_requests_in_flight() {
+        let transport = FakeTransport::default().script(
+            "a",
+            vec![
+                Ok(reply(500, "本轮次选课暂未开始")),
+                Ok(reply(500, "本轮次选课暂未开始")),
+                Ok(reply(200, "选课成功")),
+            ],
+        );
+        let mut limited = course("a");
+        limited.policy.max_attempts = Some(3);
+        // 前三个 worker 同时请求，第四个不能在它们返回前放弃
+        let options = Options {
+            workers: 4,
+            ..options()
+        };
+        let engine = Engine::new(vec![limited], options);
+
+        assert_eq!(
+            engine.start(&transport, TestTimer::new()).await,
+            StopReason::Completed
+        );
+        let snapshot = engine.snapshot();
+        assert_eq!(snapshot.courses[0].status, CourseStatus::Selected);
+        assert_eq!(snapshot.courses[0].attempts, 3);
+        assert_eq!(snapshot.courses[0].in_flight, 0);
+        assert_eq!(transport.calls.get(), 3);
+        assert_eq!(transport.answered.get(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_after_the_last_attempt_returns() {
+        let transport = FakeTransport::default();
+        let mut limited = course("a");
+        limited.policy.max_attempts = Some(2);
+        let engine = Engine::new(
+            vec![limited],
+            Options {
+                workers: 4,
+                ..options()
+            },
+        );
+        let mut events = engine.subscribe();
+
+        assert_eq!(
+            engine.start(&transport, TestTimer::new()).await,
+            StopReason::Completed
+        );
+        assert_eq!(engine.snapshot().courses[0].status, CourseStatus::GaveUp);
+        assert_eq!(transport.answered.get(), 2);
+        // 放弃之前两个请求的结果都已记录
+        let mut statuses = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let Event::Course { state, .. } = event {
+                statuses.push(state.status);
+            }
+        }
+        assert_eq!(
+            statuses,
+            [
+                CourseStatus::NotStarted,
+                CourseStatus::NotStarted,
+                CourseStatus::GaveUp
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn completion_waits_for_other_workers_in_flight() {
+        let transport = FakeTransport::default()
+            .script("a", vec![Ok(reply(200, "选课成功"))])
+            .script("b", vec![Ok(reply(200, "选课成功"))])
+            .slow("b", 500);
+        let mut a = course("a");
+        a.group = Some(0);
+        let mut b = course("b");
+        b.group = Some(0);
+        let engine = Engine::new(vec![a, b], options());
+
+        assert_eq!(
+            engine.start(&transport, TestTimer::new()).await,
+            StopReason::Completed
+        );
+        // a 选上后 b 被跳过，但已经发出的 b 请求不会被取消，晚到的成功仍然记录
+        let snapshot = engine.snapshot();
+        assert_eq!(snapshot.courses[0].status, CourseStatus::Selected);
+        assert_eq!(snapshot.courses[1].status, CourseStatus::Selected);
+        assert_eq!(transport.answered.get(), 2);
+        assert_eq!(snapshot.workers, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_at_the_deadline_and_keeps_the_others() {
+        let transport = FakeTransport::default();
+        let mut deadline = course("a");
+        deadline.policy.give_up_at_ms = Some(1000);
+        let options = Options {
+            workers: 1,
+            ..options()
+        };
+        let engine = Engine::new(vec![deadline, course("b")], options);
+
+        let run = engine.start(&transport, TestTimer::new());
+        let control = async {
+            tokio::time::sleep(std::time::Duration::from_millis(3000)).await;
+            engine.stop();
+        };
+        let (reason, ()) = futures::join!(run, control);
+
+        assert_eq!(reason, StopReason::Stopped);
+        let snapshot = engine.snapshot();
+        assert_eq!(snapshot.courses[0].status, CourseStatus::GaveUp);
+        assert!(snapshot.courses[0].attempts <= 4);
+        // 没有截止时间的 b 继续请求
+        assert_eq!(snapshot.courses[1].status, CourseStatus::NotStarted);
+        assert!(snapshot.courses[1].attempts > snapshot.courses[0].attempts);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_when_full_is_per_course() {
+        let transport = FakeTransport::default()
+            .script("a", vec![Ok(reply(500, "课容量已满"))])
+            .script(
+                "b",
+                vec![Ok(reply(500, "课容量已满")), Ok(reply(200, "选课成功"))],
+            );
+        let mut once = course("a");
+        once.policy.retry_when_full = false;
+        let options = Options {
+            workers: 1,
+            ..options()
+        };
+        let engine = Engine::new(vec![once, course("b")], options);
+
+        assert_eq!(
+            engine.start(&transport, TestTimer::new()).await,
+            StopReason::Completed
+        );
+        let snapshot = engine.snapshot();
+        assert_eq!(snapshot.courses[0].status, CourseStatus::Full);
+        assert_eq!(snapshot.courses[0].attempts, 1);
+        assert_eq!(snapshot.courses[1].status, CourseStatus::Selected);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn watch_mode_selects_only_when_a_seat_opens() {
+        let transport = FakeTransport::default()
+            .script_vacancy("a", vec![0, 0, 1])
+            .script("a", vec![Ok(reply(200, "选课成功"))]);
+        let engine = Engine::new(vec![course("a")], watching());
+        let started = tokio::time::Instant::now();
+
+        assert_eq!(
+            engine.start(&transport, TestTimer::new()).await,
+            StopReason::Completed
+        );
+        let snapshot = engine.snapshot();
+        assert_eq!(snapshot.courses[0].status, CourseStatus::Selected);
+        assert_eq!(snapshot.courses[0].checks, 3);
+        assert_eq!(snapshot.courses[0].remaining, Some(1));
+        assert_eq!(snapshot.courses[0].attempts, 1);
+        assert_eq!(transport.calls.get(), 1);
+        assert_eq!(snapshot.total_requests, 4);
+        // 两次没有空位的查询之后各等待一个查询间隔
+        assert!(started.elapsed() >= std::time::Duration::from_millis(20_000));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn watch_mode_keeps_watching_after_losing_the_race() {
+        let transport = FakeTransport::default()
+            .script_vacancy("a", vec![1, 0, 1])
+            .script(
+                "a",
+                vec![Ok(reply(500, "课容量已满")), Ok(reply(200, "选课成功"))],
+            );
+        let mut once = course("a");
+        once.policy.retry_when_full = false;
+        let engine = Engine::new(vec![once], watching());
+
+        assert_eq!(
+            engine.start(&transport, TestTimer::new()).await,
+            StopReason::Completed
+        );
+        let snapshot = engine.snapshot();
+        assert_eq!(snapshot.courses[0].status, CourseStatus::Selected);
+        assert_eq!(snapshot.courses[0].checks, 3);
+        assert_eq!(snapshot.courses[0].attempts, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn watch_mode_uses_one_worker_and_pauses_on_session_expiry() {
+        let transport = FakeTransport::default();
+        transport.vacancies.borrow_mut().insert(
+            "a".to_string(),
+            VecDeque::from([Ok(Vacancy {
+                reply: reply(401, "请先登录"),
+                remaining: None,
+            })]),
+        );
+        let engine = Engine::new(vec![course("a"), course("b")], watching());
+
+        let run = engine.start(&transport, TestTimer::new());
+        let control = async {
+            tokio::time::sleep(std::time::Duration::from_millis(60_000)).await;
+            assert_eq!(engine.snapshot().workers, 1);
+            assert_eq!(engine.phase(), Phase::Paused(PauseReason::SessionExpired));
+            assert_eq!(transport.vacancy_calls.get(), 1);
+            engine.stop();
+        };
+        let (reason, ()) = futures::join!(run, control);
+
+        assert_eq!(reason, StopReason::Stopped);
+        assert_eq!(transport.calls.get(), 0);
+        assert_eq!(
+            engine.snapshot().courses[0].status,
+            CourseStatus::NotLoggedIn
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn transport_errors_are_recorded_and_retried() {
+        let transport = FakeTransport::default().script(
+            "a",
+            vec![Err(TransportError::Timeout), Err(TransportError::Http(503))],
+        );
+        let engine = Engine::new(vec![course("a")], options());
+        let mut events = engine.subscribe();
+
+        let run = engine.start(&transport, TestTimer::new());
+        let observe = async {
+            let mut statuses = Vec::new();
+            while let Some(event) = events.next().await {
+                if let Event::Course { state, .. } = event {
+                    statuses.push(state.status);
+                    if statuses.len() == 3 {
+                        engine.stop();
+                        break;
+                    }
+                }
+            }
+            statuses
+        };
+        let (reason, statuses) = futures::join!(run, observe);
+
+        assert_eq!(reason, StopReason::Stopped);
+        assert_eq!(
+            statuses,
+            vec![
+                CourseStatus::Error(TransportError::Timeout),
+                CourseStatus::Error(TransportError::Http(503)),
+                CourseStatus::NotStarted,
+            ]
+        );
+        // 错误和上游响应分别保留
+        let state = &engine.snapshot().courses[0];
+        assert_eq!(state.last_error, Some(TransportError::Http(503)));
+        assert_eq!(state.last_code, Some(500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pause_holds_requests_until_resumed() {
+        let transport = FakeTransport::default();
+        let engine = Engine::new(vec![course("a"), course("b")], options());
+
+        let run = engine.start(&transport, TestTimer::new());
+        let control = async {
+            tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
+            engine.pause();
+            // 等待进行中的请求完成
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            let paused_at = transport.calls.get();
+            assert!(paused_at > 0);
+
+            tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
+            assert_eq!(transport.calls.get(), paused_at);
+            assert_eq!(engine.phase(), Phase::Paused(PauseReason::User));
+
+            engine.resume();
+            tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
+            assert!(transport.calls.get() > paused_at);
+            engine.stop();
+        };
+        let (reason, ()) = futures::join!(run, control);
+
+        assert_eq!(reason, StopReason::Stopped);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn backs_off_while_the_server_is_overloaded() {
+        let mut replies = vec![Err(TransportError::Http(503)); 20];
+        replies.push(Ok(reply(500, "本轮次选课暂未开始")));
+        let transport = FakeTransport::default().script("a", replies);
+        let options = Options {
+            workers: 1,
+            ..options()
+        };
+        let engine = Engine::new(vec![course("a")], options);
+
+        let run = engine.start(&transport, TestTimer::new());
+        let control = async {
+            tokio::time::sleep(std::time::Duration::from_millis(3000)).await;
+            let backoff = engine.snapshot().backoff;
+            assert_eq!(backoff.delay_ms, 1000);
+            assert!(backoff.overloaded >= 4);
+            // 固定 100ms 间隔时 3 秒内会发出 20 个请求
+            assert!(transport.calls.get() < 10);
+            engine.stop();
+        };
+        let (reason, ()) = futures::join!(run, control);
+
+        assert_eq!(reason, StopReason::Stopped);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn respects_the_requests_per_second_ceiling() {
+        let transport = FakeTransport::default();
+        let options = Options {
+            workers: 12,
+            interval_ms: 10,
+            max_rps: Some(20),
+            ..options()
+        };
+        let engine = Engine::new(vec![course("a"), course("b")], options);
+
+        let run = engine.start(&transport, TestTimer::new());
+        let control = async {
+            tokio::time::sleep(std::time::Duration::from_millis(2000)).await;
+            engine.stop();
+        };
+        futures::join!(run, control);
+
+        let calls = transport.calls.get();
+        assert!((38..=41).contains(&calls), "{calls} requests in 2s");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn emits_phase_changes() {
+        let transport = FakeTransport::default().script("a", vec![Ok(reply(200, "选课成功"))]);
+        let engine = Engine::new(vec![course("a")], options());
+        let events = engine.subscribe();
+
+        engine.start(&transport, TestTimer::new()).await;
+        drop(engine);
+
+        let phases: Vec<Phase> = events
+            .filter_map(|event| async move {
+                match event {
+                    Event::Phase(phase) => Some(phase),
+                    _ => None,
+                }
+            })
+            .collect()
+            .await;
+        assert_eq!(
+            phases,
+            vec![Phase::Running, Phase::Finished(StopReason::Completed)]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn empty_course_list_completes_immediately() {
+        let transport = FakeTransport::default();
+        let engine = Engine::new(Vec::new(), options());
+
+        assert_eq!(
+            engine.start(&transport, TestTimer::new()).await,
+            StopReason::Completed
+        );
+        assert_eq!(transport.calls.get(), 0);
+    }
+}
@@ -0,0 +1,20 @@
+// 选课引擎，负责调度选课请求、判断响应结果和维护每门课程的状态
+// 不依赖具体平台，请求和计时通过 Transport / Timer 注入
+// WebView 中使用 wasm 特性运行，测试和命令行使用 tokio 特性运行
+
+mod backoff;
+mod clock;
+mod engine;
+mod status;
+mod transport;
+
+pub use backoff::BackoffState;
+pub use clock::{ServerClock, parse_upstream_time};
+pub use engine::{CourseState, Engine, Event, Options, PauseReason, Phase, Snapshot, StopReason};
+pub use status::{Course, CoursePolicy, CourseStatus, classify};
+pub use transport::{Reply, Timer, Transport, TransportError, Vacancy};
+
+#[cfg(feature = "tokio")]
+pub use transport::TokioTimer;
+#[cfg(feature = "wasm")]
+pub use transport::WasmTimer;
@@ -0,0 +1,156 @@
+use crate::transport::{Reply, TransportError};
+use std::fmt;
+
+// 待选课程，只保留发送选课请求需要的字段
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Course {
+    // 教学班 ID (JXBID)
+    pub id: String,
+    // 课程名 (KCM)
+    pub name: String,
+    pub class_type: String,
+    pub secret_val: String,
+    // "多选一"分组，同组的课程选上一门后其余的不再请求
+    pub group: Option<usize>,
+    pub policy: CoursePolicy,
+}
+
+// 单门课程的选课策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoursePolicy {
+    // 课容量已满时继续重试
+    pub retry_when_full: bool,
+    // 最多发送的请求数，None 为不限
+    pub max_attempts: Option<u32>,
+    // 服务器时间到达这个时刻（Unix 毫秒）仍未选上时放弃
+    pub give_up_at_ms: Option<u64>,
+}
+
+impl Default for CoursePolicy {
+    fn default() -> Self {
+        Self {
+            retry_when_full: true,
+            max_attempts: None,
+            give_up_at_ms: None,
+        }
+    }
+}
+
+// 一门课程最近一次请求的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CourseStatus {
+    Waiting,
+    Selected,
+    AlreadySelected,
+    NotStarted,
+    Full,
+    InvalidParams,
+    NotLoggedIn,
+    // 同组的其他课程已经选上
+    Skipped,
+    // 达到请求次数上限或放弃时间
+    GaveUp,
+    // 其他上游响应
+    Failed { code: i64, msg: String },
+    Error(TransportError),
+}
+
+impl CourseStatus {
+    // 已经有结果，不需要再为这门课程发送请求
+    // 参数校验不通过说明请求本身有误（例如 secretVal 失效），重试也不会成功
+    pub fn is_final(&self, retry_when_full: bool) -> bool {
+        match self {
+            CourseStatus::Selected
+            | CourseStatus::AlreadySelected
+            | CourseStatus::InvalidParams
+            | CourseStatus::Skipped
+            | CourseStatus::GaveUp => true,
+            CourseStatus::Full => !retry_when_full,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for CourseStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CourseStatus::Waiting => write!(f, "等待中"),
+            CourseStatus::Selected => write!(f, "选课成功"),
+            CourseStatus::AlreadySelected => write!(f, "已选"),
+            CourseStatus::NotStarted => write!(f, "未开始"),
+            CourseStatus::Full => write!(f, "已满"),
+            CourseStatus::InvalidParams => write!(f, "参数错误"),
+            CourseStatus::NotLoggedIn => write!(f, "未登录"),
+            CourseStatus::Skipped => write!(f, "同组已选"),
+            CourseStatus::GaveUp => write!(f, "已放弃"),
+            CourseStatus::Failed { .. } => write!(f, "失败"),
+            CourseStatus::Error(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+// 根据上游的 code 和 msg 判断选课结果
+pub fn classify(reply: &Reply) -> CourseStatus {
+    match (reply.code, reply.msg.as_str()) {
+        (200, _) => CourseStatus::Selected,
+        (500, "该课程已在选课结果中") => CourseStatus::AlreadySelected,
+        (500, "本轮次选课暂未开始") => CourseStatus::NotStarted,
+        (500, "课容量已满") => CourseStatus::Full,
+        (500, "参数校验不通过") => CourseStatus::InvalidParams,
+        (401, _) => CourseStatus::NotLoggedIn,
+        (code, msg) => CourseStatus::Failed {
+            code,
+            msg: msg.to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(code: i64, msg: &str) -> Reply {
+        Reply {
+            code,
+            msg: msg.to_string(),
+            latency_ms: None,
+            date_ms: None,
+        }
+    }
+
+    #[test]
+    fn classifies_upstream_replies() {
+        let cases = [
+            (reply(200, "选课成功"), CourseStatus::Selected),
+            (
+                reply(500, "该课程已在选课结果中"),
+                CourseStatus::AlreadySelected,
+            ),
+            (reply(500, "本轮次选课暂未开始"), CourseStatus::NotStarted),
+            (reply(500, "课容量已满"), CourseStatus::Full),
+            (reply(500, "参数校验不通过"), CourseStatus::InvalidParams),
+            (reply(401, "请先登录"), CourseStatus::NotLoggedIn),
+            (
+                reply(500, "未知错误"),
+                CourseStatus::Failed {
+                    code: 500,
+                    msg: "未知错误".to_string(),
+                },
+            ),
+        ];
+        for (reply, expected) in cases {
+            assert_eq!(classify(&reply), expected, "{reply:?}");
+        }
+    }
+
+    #[test]
+    fn final_statuses() {
+        assert!(CourseStatus::Full.is_final(false));
+        assert!(!CourseStatus::Full.is_final(true));
+        assert!(CourseStatus::Selected.is_final(true));
+        assert!(CourseStatus::InvalidParams.is_final(true));
+        assert!(CourseStatus::Skipped.is_final(true));
+        assert!(CourseStatus::GaveUp.is_final(true));
+        assert!(!CourseStatus::NotStarted.is_final(false));
+    }
+}
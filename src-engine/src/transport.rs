@@ -0,0 +1,117 @@
+use crate::status::Course;
+use serde_json::Value;
+use std::fmt;
+use std::future::Future;
+
+// 上游对选课请求的响应
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reply {
+    pub code: i64,
+    pub msg: String,
+    // 本次请求的耗时，传输层无法测量时为 None
+    pub latency_ms: Option<u64>,
+    // 上游响应 Date 头对应的 Unix 毫秒时间戳，用于校准服务器时钟
+    pub date_ms: Option<u64>,
+}
+
+impl Reply {
+    // 从上游返回的 JSON 中取出 code 和 msg
+    pub fn from_json(json: &Value, latency_ms: Option<u64>) -> Self {
+        Self {
+            code: json["code"].as_i64().unwrap_or(0),
+            msg: json["msg"].as_str().unwrap_or_default().to_string(),
+            latency_ms,
+            date_ms: None,
+        }
+    }
+}
+
+// 盯课模式下查询教学班余量的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vacancy {
+    pub reply: Reply,
+    // 课容量减已选人数，上游没有返回课容量或已选人数时为 None
+    pub remaining: Option<u32>,
+}
+
+// 请求没有得到上游 JSON 响应的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransportError {
+    Timeout,
+    Connect,
+    Tls,
+    // 上游返回了非 JSON 的错误页面
+    Http(u16),
+    // 上游返回了无法解析的响应体
+    InvalidBody,
+    // 查询余量时课程列表中没有这个教学班
+    ClassNotFound,
+    Other(String),
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransportError::Timeout => write!(f, "请求超时"),
+            TransportError::Connect => write!(f, "连接失败"),
+            TransportError::Tls => write!(f, "证书错误"),
+            TransportError::Http(503) => write!(f, "服务器繁忙(503)"),
+            TransportError::Http(code) => write!(f, "HTTP {code}"),
+            TransportError::InvalidBody => write!(f, "响应异常"),
+            TransportError::ClassNotFound => write!(f, "课程列表中找不到该教学班"),
+            TransportError::Other(_) => write!(f, "请求错误"),
+        }
+    }
+}
+
+// 发送选课请求，前端通过代理实现，测试中使用预设的响应
+// 引擎停止时会丢弃进行中的 Future，实现应在此时取消请求
+pub trait Transport {
+    fn select(&self, course: &Course) -> impl Future<Output = Result<Reply, TransportError>>;
+
+    // 定时开始前的轻量请求，确认登录状态并带回上游的 Date 头
+    fn keep_alive(&self) -> impl Future<Output = Result<Reply, TransportError>>;
+
+    // 查询教学班的剩余名额，盯课模式下代替选课请求轮询
+    fn vacancy(&self, course: &Course) -> impl Future<Output = Result<Vacancy, TransportError>>;
+}
+
+// 请求间隔的计时和记录结果时间用的时钟
+pub trait Timer {
+    fn sleep(&self, ms: u64) -> impl Future<Output = ()>;
+
+    // 当前时间，Unix 毫秒时间戳
+    fn now_ms(&self) -> u64;
+}
+
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioTimer;
+
+#[cfg(feature = "tokio")]
+impl Timer for TokioTimer {
+    fn sleep(&self, ms: u64) -> impl Future<Output = ()> {
+        tokio::time::sleep(std::time::Duration::from_millis(ms))
+    }
+
+    fn now_ms(&self) -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64)
+    }
+}
+
+#[cfg(feature = "wasm")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WasmTimer;
+
+#[cfg(feature = "wasm")]
+impl Timer for WasmTimer {
+    fn sleep(&self, ms: u64) -> impl Future<Output = ()> {
+        gloo_timers::future::TimeoutFuture::new(ms.min(u32::MAX as u64) as u32)
+    }
+
+    fn now_ms(&self) -> u64 {
+        js_sys::Date::now() as u64
+    }
+}
@@ -0,0 +1,632 @@
+use actix_web::{App, HttpRequest, HttpResponse, HttpServer, web};
+use log::{debug, info};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+mod scenario;
+
+pub use scenario::{MockBatch, MockCourse, Scenario, Storm};
+
+// 1x1 透明 PNG，作为验证码图片
+const CAPTCHA_PNG: &str =
+    "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAQAAAC1HAwCAAAAC0lEQVR42mNkYAAAAAYAAjCB0C8AAAAASUVORK5CYII=";
+// 模拟登录页中的 AES 密钥
+const AES_KEY: &str = "MWMqg2tPcDkxcm11";
+
+struct MockState {
+    scenario: Scenario,
+    started: Instant,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_id: u64,
+    captchas: HashMap<String, String>,
+    tokens: HashMap<String, Instant>,
+    batch_id: Option<String>,
+    courses: Vec<MockCourse>,
+}
+
+impl MockState {
+    fn new(scenario: Scenario) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                courses: scenario.courses.clone(),
+                ..Inner::default()
+            }),
+            scenario,
+            started: Instant::now(),
+        }
+    }
+
+    fn elapsed_secs(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // 模拟上游的公共行为：延迟、503、token 过期
+    async fn gate(&self, req: &HttpRequest, needs_auth: bool) -> Result<(), HttpResponse> {
+        if self.scenario.latency_ms > 0 {
+            actix_web::rt::time::sleep(Duration::from_millis(self.scenario.latency_ms)).await;
+        }
+
+        if self.scenario.in_storm(self.elapsed_secs()) {
+            return Err(HttpResponse::ServiceUnavailable()
+                .content_type("text/html")
+                .body("<html><body><h1>503 Service Temporarily Unavailable</h1></body></html>"));
+        }
+
+        if needs_auth {
+            let token = req
+                .headers()
+                .get("Authorization")
+                .and_then(|t| t.to_str().ok())
+                .unwrap_or_default();
+            let ttl = self.scenario.token_ttl_secs;
+            let valid = self
+                .lock()
+                .tokens
+                .get(token)
+                .is_some_and(|issued| ttl == 0 || issued.elapsed().as_secs() < ttl);
+            if !valid {
+                return Err(reply(401, "登录已失效，请重新登录", Value::Null));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn reply(code: u16, msg: &str, data: Value) -> HttpResponse {
+    HttpResponse::Ok().json(json!({ "code": code, "msg": msg, "data": data }))
+}
+
+fn course_json(course: &MockCourse) -> Value {
+    json!({
+        "JXBID": course.id,
+        "KCH": course.code,
+        "KCM": course.name,
+        "SKJS": course.teacher,
+        "teachingClassType": course.class_type,
+        "secretVal": course.secret_val,
+        "KRL": course.capacity,
+        "YXRS": course.enrolled,
+        "YPSJDD": course.schedule,
+        "XQ": course.campus,
+        "XF": course.credit,
+    })
+}
+
+// 按剧本释放名额
+fn release_seats(inner: &mut Inner, elapsed_secs: u64) {
+    for course in &mut inner.courses {
+        if course
+            .free_seat_at_secs
+            .is_some_and(|at| elapsed_secs >= at)
+        {
+            course.free_seat_at_secs = None;
+            course.enrolled = course.enrolled.saturating_sub(1);
+            info!("Seat released in {} ({})", course.name, course.id);
+        }
+    }
+}
+
+async fn captcha(state: web::Data<MockState>, req: HttpRequest) -> HttpResponse {
+    if let Err(resp) = state.gate(&req, false).await {
+        return resp;
+    }
+    let mut inner = state.lock();
+    inner.next_id += 1;
+    let uuid = format!("mock-uuid-{}", inner.next_id);
+    inner
+        .captchas
+        .insert(uuid.clone(), state.scenario.captcha.clone());
+    reply(
+        200,
+        "操作成功",
+        json!({ "uuid": uuid, "captcha": format!("data:image/png;base64,{CAPTCHA_PNG}") }),
+    )
+}
+
+async fn profile_page(state: web::Data<MockState>, req: HttpRequest) -> HttpResponse {
+    if let Err(resp) = state.gate(&req, false).await {
+        return resp;
+    }
+    HttpResponse::Ok().content_type("text/html").body(format!(
+        "<html><script>loginVue.loginForm.aesKey = \"{AES_KEY}\";</script></html>"
+    ))
+}
+
+#[derive(Deserialize)]
+struct LoginForm {
+    loginname: String,
+    password: String,
+    captcha: String,
+    uuid: String,
+}
+
+async fn login(
+    state: web::Data<MockState>,
+    req: HttpRequest,
+    form: web::Form<LoginForm>,
+) -> HttpResponse {
+    if let Err(resp) = state.gate(&req, false).await {
+        return resp;
+    }
+    let mut inner = state.lock();
+    let expected = match inner.captchas.remove(&form.uuid) {
+        Some(expected) => expected,
+        None => return reply(500, "验证码已失效", Value::Null),
+    };
+    if form.captcha.is_empty() || (!expected.is_empty() && form.captcha != expected) {
+        return reply(500, "验证码错误", Value::Null);
+    }
+    if form.loginname.is_empty() || form.password.is_empty() {
+        return reply(500, "用户名或密码错误", Value::Null);
+    }
+
+    inner.next_id += 1;
+    let token = format!("mock-token-{}", inner.next_id);
+    inner.tokens.insert(token.clone(), Instant::now());
+    info!("{} logged in", form.loginname);
+
+    reply(
+        200,
+        "登录成功",
+        json!({
+            "token": token,
+            "student": {
+                "XH": form.loginname,
+                "XM": "模拟学生",
+                "electiveBatchList": state.scenario.batches,
+            }
+        }),
+    )
+}
+
+#[derive(Deserialize)]
+struct BatchForm {
+    #[serde(rename = "batchId")]
+    batch_id: String,
+}
+
+async fn set_batch(
+    state: web::Data<MockState>,
+    req: HttpRequest,
+    form: web::Form<BatchForm>,
+) -> HttpResponse {
+    if let Err(resp) = state.gate(&req, true).await {
+        return resp;
+    }
+    if !state
+        .scenario
+        .batches
+        .iter()
+        .any(|b| b.code == form.batch_id)
+    {
+        return reply(500, "选课批次不存在", Value::Null);
+    }
+    state.lock().batch_id = Some(form.batch_id.clone());
+    reply(200, "操作成功", Value::Null)
+}
+
+async fn selected_list(state: web::Data<MockState>, req: HttpRequest) -> HttpResponse {
+    if let Err(resp) = state.gate(&req, true).await {
+        return resp;
+    }
+    let inner = state.lock();
+    let courses: Vec<Value> = inner
+        .courses
+        .iter()
+        .filter(|c| c.selected)
+        .map(course_json)
+        .collect();
+    reply(200, "操作成功", json!(courses))
+}
+
+async fn favorite_list(state: web::Data<MockState>, req: HttpRequest) -> HttpResponse {
+    if let Err(resp) = state.gate(&req, true).await {
+        return resp;
+    }
+    let inner = state.lock();
+    let courses: Vec<Value> = inner
+        .courses
+        .iter()
+        .filter(|c| c.favorite)
+        .map(course_json)
+        .collect();
+    reply(200, "操作成功", json!(courses))
+}
+
+#[derive(Deserialize)]
+struct ClassListQuery {
+    #[serde(rename = "teachingClassType", default)]
+    class_type: String,
+    #[serde(rename = "pageNumber", default = "first_page")]
+    page_number: usize,
+    #[serde(rename = "pageSize", default = "page_size")]
+    page_size: usize,
+    #[serde(rename = "KEY", default)]
+    keyword: String,
+}
+
+fn first_page() -> usize {
+    1
+}
+
+fn page_size() -> usize {
+    10
+}
+
+async fn class_list(
+    state: web::Data<MockState>,
+    req: HttpRequest,
+    query: web::Json<ClassListQuery>,
+) -> HttpResponse {
+    if let Err(resp) = state.gate(&req, true).await {
+        return resp;
+    }
+    let mut inner = state.lock();
+    release_seats(&mut inner, state.elapsed_secs());
+
+    let matches: Vec<&MockCourse> = inner
+        .courses
+        .iter()
+        .filter(|c| query.class_type.is_empty() || c.class_type == query.class_type)
+        .filter(|c| {
+            query.keyword.is_empty()
+                || c.name.contains(&query.keyword)
+                || c.teacher.contains(&query.keyword)
+                || c.code.contains(&query.keyword)
+        })
+        .collect();
+    let rows: Vec<Value> = matches
+        .iter()
+        .skip(query.page_number.saturating_sub(1) * query.page_size)
+        .take(query.page_size)
+        .map(|c| course_json(c))
+        .collect();
+    reply(
+        200,
+        "操作成功",
+        json!({ "total": matches.len(), "rows": rows }),
+    )
+}
+
+#[derive(Deserialize)]
+struct AddCourseQuery {
+    #[serde(rename = "clazzType")]
+    class_type: String,
+    #[serde(rename = "clazzId")]
+    class_id: String,
+    #[serde(rename = "secretVal")]
+    secret_val: String,
+}
+
+async fn add_course(
+    state: web::Data<MockState>,
+    req: HttpRequest,
+    query: web::Query<AddCourseQuery>,
+) -> HttpResponse {
+    if let Err(resp) = state.gate(&req, true).await {
+        return resp;
+    }
+    let elapsed = state.elapsed_secs();
+    if elapsed < state.scenario.not_started_secs {
+        return reply(500, "本轮次选课暂未开始", Value::Null);
+    }
+
+    let mut inner = state.lock();
+    if inner.batch_id.is_none() {
+        return reply(500, "请先选择选课批次", Value::Null);
+    }
+    release_seats(&mut inner, elapsed);
+
+    let Some(course) = inner.courses.iter_mut().find(|c| {
+        c.id == query.class_id
+            && c.class_type == query.class_type
+            && c.secret_val == query.secret_val
+    }) else {
+        return reply(500, "参数校验不通过", Value::Null);
+    };
+
+    if course.selected {
+        return reply(500, "该课程已在选课结果中", Value::Null);
+    }
+    if course.enrolled >= course.capacity {
+        return reply(500, "课容量已满", Value::Null);
+    }
+
+    course.enrolled += 1;
+    course.selected = true;
+    info!("Enrolled in {} ({})", course.name, course.id);
+    reply(200, "选课成功", Value::Null)
+}
+
+#[derive(Deserialize)]
+struct FavoriteQuery {
+    #[serde(rename = "clazzType")]
+    class_type: String,
+    #[serde(rename = "clazzId")]
+    class_id: String,
+    #[serde(rename = "secretVal", default)]
+    secret_val: Option<String>,
+}
+
+// 收藏或取消收藏，收藏时同时校验 secretVal
+async fn set_favorite(
+    state: web::Data<MockState>,
+    req: HttpRequest,
+    query: web::Query<FavoriteQuery>,
+    favorite: bool,
+) -> HttpResponse {
+    if let Err(resp) = state.gate(&req, true).await {
+        return resp;
+    }
+    let mut inner = state.lock();
+    if inner.batch_id.is_none() {
+        return reply(500, "请先选择选课批次", Value::Null);
+    }
+    let Some(course) = inner.courses.iter_mut().find(|c| {
+        c.id == query.class_id
+            && c.class_type == query.class_type
+            && query
+                .secret_val
+                .as_ref()
+                .is_none_or(|secret_val| *secret_val == c.secret_val)
+    }) else {
+        return reply(500, "参数校验不通过", Value::Null);
+    };
+
+    match (favorite, course.favorite) {
+        (true, true) => reply(500, "该教学班已收藏", Value::Null),
+        (false, false) => reply(500, "该教学班未收藏", Value::Null),
+        _ => {
+            course.favorite = favorite;
+            info!(
+                "{} {} ({})",
+                if favorite { "Favorited" } else { "Unfavorited" },
+                course.name,
+                course.id
+            );
+            reply(200, "操作成功", Value::Null)
+        }
+    }
+}
+
+async fn favorite_add(
+    state: web::Data<MockState>,
+    req: HttpRequest,
+    query: web::Query<FavoriteQuery>,
+) -> HttpResponse {
+    if query.secret_val.is_none() {
+        return reply(500, "参数校验不通过", Value::Null);
+    }
+    set_favorite(state, req, query, true).await
+}
+
+async fn favorite_remove(
+    state: web::Data<MockState>,
+    req: HttpRequest,
+    query: web::Query<FavoriteQuery>,
+) -> HttpResponse {
+    set_favorite(state, req, query, false).await
+}
+
+async fn not_found(req: HttpRequest) -> HttpResponse {
+    debug!("Unknown path: {}", req.path());
+    HttpResponse::NotFound()
+        .content_type("text/html")
+        .body("<html><body><h1>404 Not Found</h1></body></html>")
+}
+
+// 模拟的上游路径，与代理中的操作一一对应
+fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/xsxk/auth/captcha", web::post().to(captcha))
+        .route("/xsxk/auth/login", web::post().to(login))
+        .route("/xsxk/profile/index.html", web::get().to(profile_page))
+        .route("/xsxk/elective/user", web::post().to(set_batch))
+        .route("/xsxk/elective/select", web::post().to(selected_list))
+        .route("/xsxk/sc/clazz/list", web::post().to(favorite_list))
+        .route("/xsxk/sc/clazz/add", web::post().to(favorite_add))
+        .route("/xsxk/sc/clazz/del", web::post().to(favorite_remove))
+        .route("/xsxk/elective/clazz/list", web::post().to(class_list))
+        .route("/xsxk/sc/clazz/addxk", web::post().to(add_course))
+        .default_service(web::to(not_found));
+}
+
+// 按剧本启动模拟的选课系统
+pub async fn run(scenario: Scenario) -> std::io::Result<()> {
+    let port = scenario.port;
+    info!("Starting mock icourses server at http://127.0.0.1:{port}");
+
+    let state = web::Data::new(MockState::new(scenario));
+
+    HttpServer::new(move || App::new().app_data(state.clone()).configure(routes))
+        .bind(("127.0.0.1", port))?
+        .run()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::dev::{Service, ServiceResponse};
+    use actix_web::http::StatusCode;
+    use actix_web::test::{self, TestRequest};
+    use std::path::Path;
+
+    // 加载 scenarios 目录中的剧本，并假装服务器已经运行了 elapsed_secs 秒
+    fn scenario_state(file: &str, elapsed_secs: u64) -> web::Data<MockState> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("scenarios")
+            .join(file);
+        let mut scenario = Scenario::from_file(&path).unwrap();
+        // 延迟与测试无关，去掉以免拖慢测试
+        scenario.latency_ms = 0;
+        let mut state = MockState::new(scenario);
+        state.started -= Duration::from_secs(elapsed_secs);
+        web::Data::new(state)
+    }
+
+    // 返回状态码和 JSON 响应体，响应体不是 JSON 时为 Null
+    async fn send(
+        app: &impl Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error>,
+        req: TestRequest,
+    ) -> (StatusCode, Value) {
+        let resp = test::call_service(app, req.to_request()).await;
+        let status = resp.status();
+        let body = test::read_body(resp).await;
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    // 获取验证码、登录并选择批次，返回 token
+    async fn log_in(
+        app: &impl Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error>,
+    ) -> String {
+        let (_, captcha) = send(app, TestRequest::post().uri("/xsxk/auth/captcha")).await;
+        let uuid = captcha["data"]["uuid"].as_str().unwrap();
+
+        let form = [
+            ("loginname", "2024001"),
+            ("password", "password"),
+            ("captcha", "x7k2"),
+            ("uuid", uuid),
+        ];
+        let (_, login) = send(
+            app,
+            TestRequest::post().uri("/xsxk/auth/login").set_form(form),
+        )
+        .await;
+        assert_eq!(login["code"], 200, "{login}");
+        let token = login["data"]["token"].as_str().unwrap().to_string();
+
+        let (_, batch) = send(
+            app,
+            TestRequest::post()
+                .uri("/xsxk/elective/user")
+                .insert_header(("Authorization", token.as_str()))
+                .set_form([("batchId", "mock-batch-1")]),
+        )
+        .await;
+        assert_eq!(batch["code"], 200, "{batch}");
+        token
+    }
+
+    fn add_course(token: &str, id: &str) -> TestRequest {
+        TestRequest::post()
+            .uri(&format!(
+                "/xsxk/sc/clazz/addxk?clazzType=XGKC&clazzId={id}&secretVal=secret-{id}"
+            ))
+            .insert_header(("Authorization", token))
+    }
+
+    #[actix_web::test]
+    async fn success_enrolls_once() {
+        let state = scenario_state("success.toml", 0);
+        let app = test::init_service(App::new().app_data(state).configure(routes)).await;
+        let token = log_in(&app).await;
+
+        let (_, reply) = send(&app, add_course(&token, "1001")).await;
+        assert_eq!(
+            (&reply["code"], &reply["msg"]),
+            (&json!(200), &json!("选课成功"))
+        );
+        let (_, reply) = send(&app, add_course(&token, "1001")).await;
+        assert_eq!(reply["msg"], "该课程已在选课结果中");
+
+        let (_, selected) = send(
+            &app,
+            TestRequest::post()
+                .uri("/xsxk/elective/select")
+                .insert_header(("Authorization", token.as_str())),
+        )
+        .await;
+        assert_eq!(selected["data"][0]["JXBID"], "1001");
+        assert_eq!(selected["data"][0]["YXRS"], 11);
+    }
+
+    #[actix_web::test]
+    async fn full_course_frees_a_seat_later() {
+        let state = scenario_state("full.toml", 0);
+        let app = test::init_service(App::new().app_data(state).configure(routes)).await;
+        let token = log_in(&app).await;
+        let (_, reply) = send(&app, add_course(&token, "1002")).await;
+        assert_eq!(
+            (&reply["code"], &reply["msg"]),
+            (&json!(500), &json!("课容量已满"))
+        );
+
+        // 120 秒后有人退课
+        let state = scenario_state("full.toml", 120);
+        let app = test::init_service(App::new().app_data(state).configure(routes)).await;
+        let token = log_in(&app).await;
+        let (_, reply) = send(&app, add_course(&token, "1002")).await;
+        assert_eq!(reply["code"], 200, "{reply}");
+    }
+
+    #[actix_web::test]
+    async fn storm_returns_503_while_overloaded() {
+        let state = scenario_state("storm.toml", 0);
+        let app = test::init_service(App::new().app_data(state).configure(routes)).await;
+        let token = log_in(&app).await;
+        let (_, reply) = send(&app, add_course(&token, "1001")).await;
+        assert_eq!(reply["msg"], "本轮次选课暂未开始");
+
+        // 第一段 503 期间连验证码都拿不到，响应体是 HTML
+        let state = scenario_state("storm.toml", 40);
+        let app = test::init_service(App::new().app_data(state).configure(routes)).await;
+        let (status, body) = send(&app, TestRequest::post().uri("/xsxk/auth/captcha")).await;
+        assert_eq!(
+            (status, body),
+            (StatusCode::SERVICE_UNAVAILABLE, Value::Null)
+        );
+
+        let state = scenario_state("storm.toml", 80);
+        let app = test::init_service(App::new().app_data(state).configure(routes)).await;
+        let token = log_in(&app).await;
+        let (_, reply) = send(&app, add_course(&token, "1002")).await;
+        assert_eq!(reply["code"], 200, "{reply}");
+        let (_, reply) = send(&app, add_course(&token, "1001")).await;
+        assert_eq!(reply["code"], 200, "{reply}");
+    }
+
+    #[actix_web::test]
+    async fn token_expires_after_its_ttl() {
+        let state = scenario_state("token-expiry.toml", 0);
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+        let token = log_in(&app).await;
+        let (_, reply) = send(&app, add_course(&token, "1001")).await;
+        assert_eq!(reply["msg"], "本轮次选课暂未开始");
+
+        // 让 token 在 91 秒前签发
+        for issued in state.lock().tokens.values_mut() {
+            *issued -= Duration::from_secs(91);
+        }
+        let (_, reply) = send(&app, add_course(&token, "1001")).await;
+        assert_eq!(reply["code"], 401, "{reply}");
+
+        // 重新登录后恢复
+        let token = log_in(&app).await;
+        let (_, reply) = send(&app, add_course(&token, "1001")).await;
+        assert_eq!(reply["msg"], "本轮次选课暂未开始");
+    }
+
+    #[actix_web::test]
+    async fn requests_without_login_are_rejected() {
+        let state = scenario_state("success.toml", 0);
+        let app = test::init_service(App::new().app_data(state).configure(routes)).await;
+        let (_, reply) = send(&app, add_course("mock-token-0", "1001")).await;
+        assert_eq!(reply["code"], 401);
+
+        let (status, _) = send(&app, TestRequest::post().uri("/xsxk/unknown")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,30 @@
+use clap::Parser;
+use funky_lesson_mock::Scenario;
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(name = "funky_lesson_mock", about = "用于离线测试的模拟选课系统")]
+struct Cli {
+    /// 剧本文件，见 src-mock/scenarios
+    #[arg(short, long)]
+    scenario: Option<PathBuf>,
+    /// 监听端口，覆盖剧本中的设置
+    #[arg(short, long)]
+    port: Option<u16>,
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    env_logger::init_from_env(env_logger::Env::default().default_filter_or("info"));
+
+    let cli = Cli::parse();
+    let mut scenario = match &cli.scenario {
+        Some(path) => Scenario::from_file(path)?,
+        None => Scenario::default(),
+    };
+    if let Some(port) = cli.port {
+        scenario.port = port;
+    }
+
+    funky_lesson_mock::run(scenario).await
+}
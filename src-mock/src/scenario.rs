@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+
+// 模拟服务器的剧本，所有时间都相对于服务器启动时刻
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scenario {
+    pub port: u16,
+    // 正确的验证码，为空时接受任意非空验证码
+    pub captcha: String,
+    // 每个请求额外增加的延迟
+    pub latency_ms: u64,
+    // 启动后这段时间内选课返回“本轮次选课暂未开始”
+    pub not_started_secs: u64,
+    // token 的有效期，0 表示永不过期
+    pub token_ttl_secs: u64,
+    // 503 时间窗口
+    pub storms: Vec<Storm>,
+    pub batches: Vec<MockBatch>,
+    pub courses: Vec<MockCourse>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Storm {
+    pub start_secs: u64,
+    pub duration_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MockBatch {
+    pub code: String,
+    pub name: String,
+    #[serde(rename = "beginTime")]
+    pub begin_time: String,
+    #[serde(rename = "endTime")]
+    pub end_time: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MockCourse {
+    pub id: String,
+    pub code: String,
+    pub name: String,
+    pub teacher: String,
+    #[serde(default = "default_class_type")]
+    pub class_type: String,
+    #[serde(default)]
+    pub secret_val: String,
+    pub capacity: u32,
+    #[serde(default)]
+    pub enrolled: u32,
+    // 例如 "1-16周 星期一 第1-2节 逸夫楼A101"
+    #[serde(default)]
+    pub schedule: String,
+    #[serde(default)]
+    pub campus: String,
+    #[serde(default)]
+    pub credit: f32,
+    // 是否出现在收藏列表中
+    #[serde(default)]
+    pub favorite: bool,
+    // 是否已经选上
+    #[serde(default)]
+    pub selected: bool,
+    // 到达该时刻时释放一个名额，用于模拟退课
+    #[serde(default)]
+    pub free_seat_at_secs: Option<u64>,
+}
+
+fn default_class_type() -> String {
+    "XGKC".to_string()
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        let course =
+            |id: &str, name: &str, teacher: &str, capacity, enrolled, schedule: &str| MockCourse {
+                id: id.to_string(),
+                code: format!("KC{id}"),
+                name: name.to_string(),
+                teacher: teacher.to_string(),
+                class_type: default_class_type(),
+                secret_val: format!("secret-{id}"),
+                capacity,
+                enrolled,
+                schedule: schedule.to_string(),
+                campus: "前卫南区".to_string(),
+                credit: 2.0,
+                favorite: true,
+                selected: false,
+                free_seat_at_secs: None,
+            };
+
+        Self {
+            port: 8080,
+            captcha: String::new(),
+            latency_ms: 0,
+            not_started_secs: 0,
+            token_ttl_secs: 0,
+            storms: Vec::new(),
+            batches: vec![MockBatch {
+                code: "mock-batch-1".to_string(),
+                name: "模拟选课批次".to_string(),
+                begin_time: "2025-09-01 10:00:00".to_string(),
+                end_time: "2025-09-07 16:00:00".to_string(),
+            }],
+            courses: vec![
+                course(
+                    "1001",
+                    "模拟课程A",
+                    "张老师",
+                    30,
+                    10,
+                    "1-16周 星期一 第1-2节 逸夫楼A101",
+                ),
+                course(
+                    "1002",
+                    "模拟课程B",
+                    "李老师",
+                    30,
+                    30,
+                    "1-16周 星期三 第3-4节 逸夫楼B203",
+                ),
+                course(
+                    "1003",
+                    "模拟课程C",
+                    "王老师",
+                    60,
+                    20,
+                    "1-8周 星期五 第5-6节 经信教学楼C301",
+                ),
+            ],
+        }
+    }
+}
+
+impl Scenario {
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        toml::from_str(&text).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {e}", path.display()),
+            )
+        })
+    }
+
+    pub fn in_storm(&self, elapsed_secs: u64) -> bool {
+        self.storms.iter().any(|storm| {
+            elapsed_secs >= storm.start_secs
+                && elapsed_secs < storm.start_secs + storm.duration_secs
+        })
+    }
+}
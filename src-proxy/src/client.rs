@@ -0,0 +1,294 @@
+use crate::config::ProxyConfig;
+use crate::error::ProxyError;
+use crate::operation::{Method, Operation, OperationParams, UpstreamRequest};
+use crate::redact;
+use log::{debug, error, info, warn};
+use reqwest::cookie::{CookieStore, Jar};
+use reqwest::header::{AUTHORIZATION, DATE, HeaderMap, HeaderValue};
+use reqwest::{Certificate, Url};
+use serde_json::Value;
+use std::io;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+// 可清空的 cookie 存储，清空时不需要重建 Client，连接池得以保留
+#[derive(Default)]
+struct SessionJar(RwLock<Jar>);
+
+impl SessionJar {
+    fn reset(&self) {
+        if let Ok(mut jar) = self.0.write() {
+            *jar = Jar::default();
+        }
+    }
+}
+
+impl CookieStore for SessionJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        if let Ok(jar) = self.0.read() {
+            jar.set_cookies(cookie_headers, url);
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        self.0.read().ok().and_then(|jar| jar.cookies(url))
+    }
+}
+
+// 上游响应体，POST 操作为 JSON，GET 操作为页面文本
+#[derive(Debug)]
+pub enum UpstreamBody {
+    Json(Value),
+    Text(String),
+}
+
+#[derive(Debug)]
+pub struct UpstreamResponse {
+    // 上游返回的 HTTP 状态码
+    pub status: u16,
+    // 上游响应的 Date 头，前端用来估计服务器时钟
+    pub date: Option<String>,
+    pub body: UpstreamBody,
+}
+
+// 每个代理实例共享一个上游 Client（通过 web::Data 注入）
+// 复用 TLS 会话和 keep-alive 连接，并持有会话 cookie
+pub struct UpstreamClient {
+    client: reqwest::Client,
+    jar: Arc<SessionJar>,
+    base: String,
+    origin: String,
+}
+
+impl UpstreamClient {
+    pub fn new(config: &ProxyConfig) -> io::Result<Self> {
+        config.validate()?;
+        let tls = &config.tls;
+        let jar = Arc::new(SessionJar::default());
+        let mut builder = reqwest::Client::builder();
+
+        if let Some(path) = &tls.ca_bundle {
+            let pem = std::fs::read(path)?;
+            let certs = Certificate::from_pem_bundle(&pem).map_err(io::Error::other)?;
+            if certs.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("no certificates found in {}", path.display()),
+                ));
+            }
+            info!(
+                "Loaded {} extra CA certificate(s) from {}",
+                certs.len(),
+                path.display()
+            );
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+            if tls.pin_ca_bundle {
+                info!("Upstream certificates must chain to the configured CA bundle");
+                builder = builder.tls_built_in_root_certs(false);
+            }
+        } else if tls.pin_ca_bundle {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "pin_ca_bundle requires a ca_bundle",
+            ));
+        }
+
+        if tls.accept_invalid_certs {
+            warn!("==================================================================");
+            warn!("TLS CERTIFICATE VERIFICATION IS DISABLED FOR UPSTREAM REQUESTS.");
+            warn!("Passwords and tokens can be intercepted on untrusted networks.");
+            warn!("Only use this when the upstream certificate is known to be broken.");
+            warn!("==================================================================");
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+
+        let client = builder
+            .cookie_provider(jar.clone())
+            .pool_max_idle_per_host(32)
+            .pool_idle_timeout(Duration::from_secs(90))
+            .tcp_keepalive(Duration::from_secs(30))
+            .connect_timeout(Duration::from_secs(10))
+            .timeout(Duration::from_secs(15))
+            .build()
+            .map_err(io::Error::other)?;
+        Ok(Self {
+            client,
+            jar,
+            base: config.upstream_base.clone(),
+            origin: config.upstream_origin(),
+        })
+    }
+
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    // 退出登录时清空 cookie
+    pub fn reset_session(&self) {
+        self.jar.reset();
+    }
+
+    // 执行一个具名操作，HTTP 代理和 Tauri 命令共用
+    // token 为前端持有的上游 Authorization
+    pub async fn execute(
+        &self,
+        op: Operation,
+        params: OperationParams,
+        token: Option<&str>,
+    ) -> Result<UpstreamResponse, ProxyError> {
+        let upstream = UpstreamRequest::build(op, params, &self.base)?;
+        self.send(upstream, token).await
+    }
+
+    async fn send(
+        &self,
+        upstream: UpstreamRequest,
+        token: Option<&str>,
+    ) -> Result<UpstreamResponse, ProxyError> {
+        let mut headers = HeaderMap::new();
+        // 配置常用headers
+        headers.insert("User-Agent", HeaderValue::from_static("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/139.0.0.0 Safari/537.36"));
+        if let Ok(origin) = HeaderValue::from_str(self.origin()) {
+            headers.insert("Origin", origin);
+        }
+
+        if let Some(token) = token {
+            let token = HeaderValue::from_str(token).map_err(|e| {
+                error!("Invalid authorization token: {e}");
+                ProxyError::InvalidHeader("Authorization")
+            })?;
+            headers.insert(AUTHORIZATION, token);
+        }
+        // batch_id 已经过校验，只包含标识符字符
+        if let Some(batch_id) = &upstream.batch_id {
+            if let Ok(value) = HeaderValue::from_str(batch_id) {
+                headers.insert("batchId", value);
+            }
+            if let Ok(value) = HeaderValue::from_str(&format!(
+                "{}/xsxk/profile/index.html?batchId={batch_id}",
+                self.base()
+            )) {
+                headers.insert("Referer", value);
+            }
+        }
+
+        debug!("Sending request to: {}", upstream.url);
+        debug!("Headers: {:?}", redact::headers(&headers));
+        debug!("Query params: {:?}", redact::pairs(&upstream.query));
+        debug!("Request body: {:?}", redact::pairs(&upstream.form));
+
+        let client = &self.client;
+        let mut request = match upstream.method {
+            Method::Get => client.get(&upstream.url),
+            Method::Post => client.post(&upstream.url),
+        }
+        .headers(headers);
+
+        // 添加请求体或查询参数
+        if !upstream.form.is_empty() {
+            // 应当以urlencoded形式发送
+            request = request.form(&upstream.form);
+        }
+        if let Some(json_body) = &upstream.json {
+            request = request.json(json_body);
+        }
+        if !upstream.query.is_empty() {
+            request = request.query(&upstream.query);
+        }
+
+        // 错误信息中的 URL 可能带有 secretVal 等查询参数，记录前去掉
+        let response = request.send().await.map_err(|e| {
+            let e = e.without_url();
+            error!("Request to {} failed: {e}", upstream.url);
+            ProxyError::from_transport(&e)
+        })?;
+
+        let status = response.status();
+        debug!("Received response with status: {status}");
+        let date = response
+            .headers()
+            .get(DATE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+
+        let text = response.text().await.map_err(|e| {
+            let e = e.without_url();
+            error!("Failed to get response text: {e}");
+            ProxyError::from_body(&e, status.as_u16())
+        })?;
+
+        if upstream.method == Method::Get {
+            if !status.is_success() {
+                return Err(upstream_http(status, &text));
+            }
+            return Ok(UpstreamResponse {
+                status: status.as_u16(),
+                date,
+                body: UpstreamBody::Text(text),
+            });
+        }
+
+        debug!("Response text: {}", redact::body(&text));
+        match serde_json::from_str::<Value>(&text) {
+            Ok(json_value) => Ok(UpstreamResponse {
+                status: status.as_u16(),
+                date,
+                body: UpstreamBody::Json(json_value),
+            }),
+            Err(e) if status.is_success() => {
+                warn!("Failed to parse response as JSON: {e}");
+                Err(ProxyError::NonJsonBody(snippet(&text)))
+            }
+            Err(_) => {
+                warn!("Upstream returned HTTP {status} with a non-JSON body");
+                Err(upstream_http(status, &text))
+            }
+        }
+    }
+}
+
+fn upstream_http(status: reqwest::StatusCode, text: &str) -> ProxyError {
+    ProxyError::UpstreamHttp {
+        status: status.as_u16(),
+        message: snippet(text),
+    }
+}
+
+// 截取响应体开头用于错误信息
+fn snippet(text: &str) -> String {
+    text.trim().chars().take(200).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reset_session_clears_cookies() {
+        let client = UpstreamClient::new(&ProxyConfig::default()).unwrap();
+        let url = Url::parse(client.base()).unwrap();
+        let cookie = HeaderValue::from_static("JSESSIONID=abc; Path=/");
+        client.jar.set_cookies(&mut std::iter::once(&cookie), &url);
+        assert_eq!(
+            client.jar.cookies(&url),
+            Some(HeaderValue::from_static("JSESSIONID=abc"))
+        );
+
+        client.reset_session();
+        assert_eq!(client.jar.cookies(&url), None);
+
+        // 清空后仍然可以继续保存新的 cookie
+        client.jar.set_cookies(&mut std::iter::once(&cookie), &url);
+        assert!(client.jar.cookies(&url).is_some());
+    }
+}
@@ -0,0 +1,301 @@
+use reqwest::Url;
+use serde::Deserialize;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_CONFIG_FILE: &str = "funky-proxy.toml";
+
+// 代理配置，优先级: 默认值 < 配置文件 < 环境变量 < 命令行参数
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProxyConfig {
+    pub bind: IpAddr,
+    pub port: u16,
+    // 上游选课系统地址，可指向本地测试服务器
+    pub upstream_base: String,
+    pub cors_origins: Vec<String>,
+    pub log_level: String,
+    // 日志文件路径，设置后日志同时写入该文件，超过 2 MB 时滚动，保留最近 3 个旧文件
+    pub log_file: Option<PathBuf>,
+    // 共享密钥，设置后所有请求都必须携带 X-Funky-Secret 请求头
+    // Tauri 每次启动都会随机生成一个，独立运行时可通过配置文件或环境变量指定
+    pub shared_secret: Option<String>,
+    pub tls: TlsOptions,
+}
+
+// 上游 TLS 设置，默认开启证书校验
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsOptions {
+    // 额外信任的 PEM 证书包
+    pub ca_bundle: Option<PathBuf>,
+    // 只信任 ca_bundle 中的证书，不再使用内置根证书
+    pub pin_ca_bundle: bool,
+    // 关闭证书校验，仅用于证书环境异常的情况
+    pub accept_invalid_certs: bool,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        Self {
+            bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 3030,
+            upstream_base: "https://icourses.jlu.edu.cn".to_string(),
+            cors_origins: vec![
+                "http://tauri.localhost".to_string(),
+                "http://localhost:1420".to_string(),
+                "http://127.0.0.1:1420".to_string(),
+            ],
+            log_level: "info".to_string(),
+            log_file: None,
+            shared_secret: None,
+            tls: TlsOptions::default(),
+        }
+    }
+}
+
+impl ProxyConfig {
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        toml::from_str(&text).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {e}", path.display()),
+            )
+        })
+    }
+
+    // 读取配置文件（未指定时尝试当前目录下的 funky-proxy.toml），再应用环境变量
+    pub fn load(path: Option<&Path>) -> io::Result<Self> {
+        let mut config = match path {
+            Some(path) => Self::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).is_file() => {
+                Self::from_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            None => Self::default(),
+        };
+        config.apply_env()?;
+        config.normalize();
+        Ok(config)
+    }
+
+    // FUNKY_PROXY_BIND / _PORT / _UPSTREAM / _CORS_ORIGINS / _LOG / _LOG_FILE / _SECRET
+    // FUNKY_PROXY_CA_BUNDLE / _PIN_CA_BUNDLE / _INSECURE_TLS
+    pub fn apply_env(&mut self) -> io::Result<()> {
+        self.apply_vars(|name| std::env::var(name).ok())
+    }
+
+    // 从 lookup 读取 FUNKY_PROXY_* 变量，测试中不必修改进程环境
+    fn apply_vars(&mut self, lookup: impl Fn(&str) -> Option<String>) -> io::Result<()> {
+        let var = |name: &str| lookup(&format!("FUNKY_PROXY_{name}"));
+        let flag = |name: &str| var(name).map(|v| matches!(v.as_str(), "1" | "true" | "yes"));
+        let invalid =
+            |name: &str| io::Error::new(io::ErrorKind::InvalidInput, format!("FUNKY_PROXY_{name}"));
+
+        if let Some(bind) = var("BIND") {
+            self.bind = bind.parse().map_err(|_| invalid("BIND"))?;
+        }
+        if let Some(port) = var("PORT") {
+            self.port = port.parse().map_err(|_| invalid("PORT"))?;
+        }
+        if let Some(upstream) = var("UPSTREAM") {
+            self.upstream_base = upstream;
+        }
+        if let Some(origins) = var("CORS_ORIGINS") {
+            self.cors_origins = origins
+                .split(',')
+                .map(str::trim)
+                .filter(|o| !o.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+        if let Some(level) = var("LOG") {
+            self.log_level = level;
+        }
+        if let Some(path) = var("LOG_FILE") {
+            self.log_file = Some(PathBuf::from(path));
+        }
+        if let Some(secret) = var("SECRET") {
+            self.shared_secret = Some(secret);
+        }
+        if let Some(path) = var("CA_BUNDLE") {
+            self.tls.ca_bundle = Some(PathBuf::from(path));
+        }
+        if let Some(pin) = flag("PIN_CA_BUNDLE") {
+            self.tls.pin_ca_bundle = pin;
+        }
+        if let Some(insecure) = flag("INSECURE_TLS") {
+            self.tls.accept_invalid_certs = insecure;
+        }
+        Ok(())
+    }
+
+    // 去掉 upstream_base 末尾的 /，拼接路径时不会出现 //
+    // 修改 upstream_base 后需要再调用一次
+    pub fn normalize(&mut self) {
+        self.upstream_base = self.upstream_base.trim_end_matches('/').to_string();
+    }
+
+    // 只检查不修改，创建上游客户端和启动服务器时调用
+    pub fn validate(&self) -> io::Result<()> {
+        let url = Url::parse(&self.upstream_base).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid upstream_base `{}`: {e}", self.upstream_base),
+            )
+        })?;
+        if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "upstream_base must be an http(s) URL: {}",
+                    self.upstream_base
+                ),
+            ));
+        }
+        if self.shared_secret.as_deref().is_some_and(str::is_empty) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "shared_secret must not be empty",
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind, self.port)
+    }
+
+    // 上游的 Origin，例如 https://icourses.jlu.edu.cn
+    pub fn upstream_origin(&self) -> String {
+        Url::parse(&self.upstream_base)
+            .map(|url| url.origin().ascii_serialization())
+            .unwrap_or_else(|_| self.upstream_base.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    // 写入临时目录下的配置文件，文件名带上测试名和进程号，并行运行时互不影响
+    fn config_file(name: &str, text: &str) -> ProxyConfig {
+        let path =
+            std::env::temp_dir().join(format!("funky-proxy-{name}-{}.toml", std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        let config = ProxyConfig::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        config.unwrap()
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let map: HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| map.get(name).cloned()
+    }
+
+    #[test]
+    fn file_overrides_defaults() {
+        let config = config_file(
+            "file",
+            r#"
+                port = 8080
+                upstream_base = "http://127.0.0.1:9000/"
+
+                [tls]
+                pin_ca_bundle = true
+            "#,
+        );
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.upstream_base, "http://127.0.0.1:9000/");
+        assert!(config.tls.pin_ca_bundle);
+        // 文件中没有的字段保留默认值
+        assert_eq!(config.bind, ProxyConfig::default().bind);
+        assert_eq!(config.cors_origins, ProxyConfig::default().cors_origins);
+    }
+
+    #[test]
+    fn unknown_file_fields_are_rejected() {
+        let path =
+            std::env::temp_dir().join(format!("funky-proxy-unknown-{}.toml", std::process::id()));
+        std::fs::write(&path, "original_url = \"https://example.com\"\n").unwrap();
+        let result = ProxyConfig::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn env_overrides_file() {
+        let mut config = config_file("env", "port = 8080\nlog_level = \"debug\"\n");
+        config
+            .apply_vars(vars(&[
+                ("FUNKY_PROXY_PORT", "9090"),
+                (
+                    "FUNKY_PROXY_CORS_ORIGINS",
+                    " http://a.test , ,http://b.test",
+                ),
+                ("FUNKY_PROXY_SECRET", "s3cret"),
+                ("FUNKY_PROXY_INSECURE_TLS", "yes"),
+            ]))
+            .unwrap();
+        assert_eq!(config.port, 9090);
+        assert_eq!(config.cors_origins, ["http://a.test", "http://b.test"]);
+        assert_eq!(config.shared_secret.as_deref(), Some("s3cret"));
+        assert!(config.tls.accept_invalid_certs);
+        // 没有设置的环境变量不覆盖文件中的值
+        assert_eq!(config.log_level, "debug");
+    }
+
+    #[test]
+    fn invalid_env_values_are_rejected() {
+        for (name, value) in [
+            ("FUNKY_PROXY_PORT", "70000"),
+            ("FUNKY_PROXY_BIND", "localhost"),
+        ] {
+            let mut config = ProxyConfig::default();
+            let e = config.apply_vars(vars(&[(name, value)])).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+            assert_eq!(e.to_string(), name);
+        }
+    }
+
+    #[test]
+    fn normalize_trims_trailing_slashes() {
+        let mut config = ProxyConfig {
+            upstream_base: "https://icourses.jlu.edu.cn//".to_string(),
+            ..ProxyConfig::default()
+        };
+        config.normalize();
+        assert_eq!(config.upstream_base, "https://icourses.jlu.edu.cn");
+        assert_eq!(config.upstream_origin(), "https://icourses.jlu.edu.cn");
+    }
+
+    #[test]
+    fn validate_rejects_invalid_values_without_changing_them() {
+        let cases = [
+            ProxyConfig {
+                upstream_base: "icourses.jlu.edu.cn/".to_string(),
+                ..ProxyConfig::default()
+            },
+            ProxyConfig {
+                upstream_base: "ftp://icourses.jlu.edu.cn/".to_string(),
+                ..ProxyConfig::default()
+            },
+            ProxyConfig {
+                shared_secret: Some(String::new()),
+                ..ProxyConfig::default()
+            },
+        ];
+        for config in cases {
+            let before = format!("{config:?}");
+            let e = config.validate().unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+            assert_eq!(format!("{config:?}"), before);
+        }
+        assert!(ProxyConfig::default().validate().is_ok());
+    }
+}
@@ -0,0 +1,264 @@
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use serde_json::{Value, json};
+use std::error::Error as _;
+use std::fmt;
+
+// 代理返回给前端的结构化错误
+// 统一格式: {"error": {"kind": "...", "message": "...", "status": 503}}
+// 其中 status 只在 upstream_http 时出现，表示上游返回的 HTTP 状态码
+#[derive(Debug)]
+pub enum ProxyError {
+    // 不在白名单中的操作
+    UnknownOperation(String),
+    // 参数缺失或不合法
+    InvalidParams(String),
+    // 请求头不合法
+    InvalidHeader(&'static str),
+    // 缺少或不匹配本次启动的共享密钥
+    Unauthorized,
+    // 该操作不支持此 HTTP 方法
+    MethodNotAllowed(String),
+    // 上游请求超时
+    Timeout(String),
+    // 无法连接上游（DNS、拒绝连接、连接中断等）
+    Connect(String),
+    // TLS 握手或证书校验失败
+    Tls(String),
+    // 上游返回了非成功状态码且响应体不是 JSON，或读取响应体失败
+    UpstreamHttp { status: u16, message: String },
+    // 上游返回了成功状态码，但响应体不是 JSON
+    NonJsonBody(String),
+}
+
+impl ProxyError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ProxyError::UnknownOperation(_) => "unknown_operation",
+            ProxyError::InvalidParams(_) => "invalid_params",
+            ProxyError::InvalidHeader(_) => "invalid_header",
+            ProxyError::Unauthorized => "unauthorized",
+            ProxyError::MethodNotAllowed(_) => "method_not_allowed",
+            ProxyError::Timeout(_) => "timeout",
+            ProxyError::Connect(_) => "connect",
+            ProxyError::Tls(_) => "tls",
+            ProxyError::UpstreamHttp { .. } => "upstream_http",
+            ProxyError::NonJsonBody(_) => "non_json_body",
+        }
+    }
+
+    // 错误信封，HTTP 响应和 Tauri 命令的错误返回值共用
+    pub fn envelope(&self) -> Value {
+        let mut error = json!({
+            "kind": self.kind(),
+            "message": self.to_string(),
+        });
+        if let ProxyError::UpstreamHttp { status, .. } = self {
+            error["status"] = json!(status);
+        }
+        json!({ "error": error })
+    }
+
+    // 将发送请求时 reqwest 的传输错误归类
+    pub fn from_transport(e: &reqwest::Error) -> Self {
+        let message = e.to_string();
+        if e.is_timeout() {
+            return ProxyError::Timeout(message);
+        }
+        if e.is_connect()
+            && let Some(tls) = tls_source(e)
+        {
+            return ProxyError::Tls(format!("{message}: {tls}"));
+        }
+        ProxyError::Connect(message)
+    }
+
+    // 已经收到上游状态码后读取响应体失败，连接本身是成功的，所以按 upstream_http 返回
+    pub fn from_body(e: &reqwest::Error, status: u16) -> Self {
+        if e.is_timeout() {
+            return ProxyError::Timeout(e.to_string());
+        }
+        ProxyError::UpstreamHttp {
+            status,
+            message: format!("failed to read response body: {e}"),
+        }
+    }
+}
+
+// 在错误链中查找 rustls 的错误
+// tokio-rustls 把它包在 io::Error 中，而 io::Error::source 会跳过被包装的错误，需要用 get_ref 取出
+fn tls_source(e: &reqwest::Error) -> Option<&rustls::Error> {
+    let mut source = e.source();
+    while let Some(inner) = source {
+        if let Some(tls) = inner.downcast_ref::<rustls::Error>() {
+            return Some(tls);
+        }
+        if let Some(tls) = inner
+            .downcast_ref::<std::io::Error>()
+            .and_then(std::io::Error::get_ref)
+            .and_then(|io| io.downcast_ref::<rustls::Error>())
+        {
+            return Some(tls);
+        }
+        source = inner.source();
+    }
+    None
+}
+
+impl fmt::Display for ProxyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProxyError::UnknownOperation(op) => write!(f, "Unknown operation: {op}"),
+            ProxyError::InvalidParams(msg) => write!(f, "Invalid parameters: {msg}"),
+            ProxyError::InvalidHeader(name) => write!(f, "Invalid {name} header"),
+            ProxyError::Unauthorized => write!(f, "Missing or invalid proxy secret"),
+            ProxyError::MethodNotAllowed(op) => {
+                write!(f, "Method not allowed for operation: {op}")
+            }
+            ProxyError::Timeout(msg) => write!(f, "Upstream request timed out: {msg}"),
+            ProxyError::Connect(msg) => write!(f, "Failed to reach upstream: {msg}"),
+            ProxyError::Tls(msg) => write!(f, "TLS error: {msg}"),
+            ProxyError::UpstreamHttp { status, message } => {
+                write!(f, "Upstream returned HTTP {status}: {message}")
+            }
+            ProxyError::NonJsonBody(msg) => write!(f, "Upstream returned a non-JSON body: {msg}"),
+        }
+    }
+}
+
+impl ResponseError for ProxyError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ProxyError::UnknownOperation(_) => StatusCode::NOT_FOUND,
+            ProxyError::InvalidParams(_) | ProxyError::InvalidHeader(_) => StatusCode::BAD_REQUEST,
+            ProxyError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ProxyError::MethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
+            ProxyError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            ProxyError::Connect(_) | ProxyError::Tls(_) | ProxyError::NonJsonBody(_) => {
+                StatusCode::BAD_GATEWAY
+            }
+            // 读取响应体失败时 status 可能是成功状态码，不能原样返回
+            ProxyError::UpstreamHttp { status, .. } => StatusCode::from_u16(*status)
+                .ok()
+                .filter(|status| status.is_client_error() || status.is_server_error())
+                .unwrap_or(StatusCode::BAD_GATEWAY),
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(self.envelope())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    fn upstream_http(status: u16) -> ProxyError {
+        ProxyError::UpstreamHttp {
+            status,
+            message: "busy".to_string(),
+        }
+    }
+
+    #[test]
+    fn envelope_carries_kind_and_message() {
+        let cases = [
+            (
+                ProxyError::UnknownOperation("x".into()),
+                "unknown_operation",
+            ),
+            (ProxyError::InvalidParams("x".into()), "invalid_params"),
+            (ProxyError::InvalidHeader("Authorization"), "invalid_header"),
+            (ProxyError::Unauthorized, "unauthorized"),
+            (
+                ProxyError::MethodNotAllowed("x".into()),
+                "method_not_allowed",
+            ),
+            (ProxyError::Timeout("x".into()), "timeout"),
+            (ProxyError::Connect("x".into()), "connect"),
+            (ProxyError::Tls("x".into()), "tls"),
+            (ProxyError::NonJsonBody("x".into()), "non_json_body"),
+        ];
+        for (error, kind) in cases {
+            assert_eq!(error.kind(), kind);
+            assert_eq!(
+                error.envelope(),
+                json!({ "error": { "kind": kind, "message": error.to_string() } })
+            );
+        }
+
+        let error = upstream_http(503);
+        assert_eq!(
+            error.envelope(),
+            json!({ "error": {
+                "kind": "upstream_http",
+                "message": "Upstream returned HTTP 503: busy",
+                "status": 503,
+            } })
+        );
+    }
+
+    #[test]
+    fn status_code_follows_error_kind() {
+        assert_eq!(
+            ProxyError::UnknownOperation("x".into()).status_code(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            ProxyError::Unauthorized.status_code(),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            ProxyError::Timeout("x".into()).status_code(),
+            StatusCode::GATEWAY_TIMEOUT
+        );
+        assert_eq!(
+            ProxyError::Tls("x".into()).status_code(),
+            StatusCode::BAD_GATEWAY
+        );
+        assert_eq!(
+            upstream_http(503).status_code(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(upstream_http(404).status_code(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn successful_upstream_status_becomes_bad_gateway() {
+        assert_eq!(upstream_http(200).status_code(), StatusCode::BAD_GATEWAY);
+        assert_eq!(upstream_http(302).status_code(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[actix_web::test]
+    async fn closed_port_is_a_connect_error() {
+        // 绑定后立即释放，得到一个没有监听的端口
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let e = reqwest::get(format!("http://127.0.0.1:{port}/"))
+            .await
+            .unwrap_err();
+        let error = ProxyError::from_transport(&e);
+        assert!(matches!(error, ProxyError::Connect(_)), "{error:?}");
+    }
+
+    #[actix_web::test]
+    async fn unanswered_request_is_a_timeout() {
+        // 监听但从不响应，连接由系统完成，请求一直等不到响应
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let e = reqwest::Client::new()
+            .get(format!("http://{addr}/"))
+            .timeout(Duration::from_millis(200))
+            .send()
+            .await
+            .unwrap_err();
+        let error = ProxyError::from_transport(&e);
+        assert!(matches!(error, ProxyError::Timeout(_)), "{error:?}");
+    }
+}
@@ -1,23 +1,83 @@
 use actix_cors::Cors;
+use actix_web::dev::Server;
+use actix_web::middleware::from_fn;
 use actix_web::{App, HttpRequest, HttpResponse, HttpServer, web};
 use log::{debug, error, info, warn};
-use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderValue};
-use serde::Deserialize;
-use serde_json::json;
-use std::collections::HashMap;
+use std::net::SocketAddr;
+
+mod client;
+mod config;
+mod error;
+mod logfile;
+mod operation;
+mod redact;
+mod secret;
+mod server;
+
+pub use client::{UpstreamBody, UpstreamClient, UpstreamResponse};
+pub use config::{DEFAULT_CONFIG_FILE, ProxyConfig, TlsOptions};
+pub use error::ProxyError;
+pub use operation::{Method, Operation, OperationParams, UpstreamRequest};
+pub use secret::SECRET_HEADER;
+pub use server::{ProxyHandle, ProxyServer};
+
+use secret::{SharedSecret, require_secret};
+
+// 转发上游 Date 头使用的响应头，前端据此估计服务器时钟
+pub const UPSTREAM_DATE_HEADER: &str = "X-Upstream-Date";
+
+// 使用默认配置文件和环境变量启动代理
+pub fn main() -> std::io::Result<()> {
+    serve(ProxyConfig::load(None)?)
+}
+
+// 使用给定配置启动代理，阻塞直到服务器退出
+pub fn serve(config: ProxyConfig) -> std::io::Result<()> {
+    init_logger(&config);
+    actix_web::rt::System::new().block_on(run(config))
+}
+
+pub async fn run(config: ProxyConfig) -> std::io::Result<()> {
+    let (server, _) = http_server(&config)?;
+    server.await
+}
 
-#[actix_web::main]
-pub async fn main() -> std::io::Result<()> {
-    env_logger::init_from_env(env_logger::Env::default().default_filter_or("debug"));
+// 初始化日志，RUST_LOG 优先于配置中的 log_level
+// 配置了 log_file 时日志同时写入文件，文件无法打开时只输出到标准错误
+// 已经初始化过时不做任何事
+pub fn init_logger(config: &ProxyConfig) {
+    let file = config.log_file.as_deref().and_then(|path| {
+        match logfile::RotatingFile::open(path, logfile::MAX_BYTES) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                eprintln!("Failed to open log file {}: {e}", path.display());
+                None
+            }
+        }
+    });
+    let _ = env_logger::Builder::from_env(
+        env_logger::Env::default().default_filter_or(config.log_level.as_str()),
+    )
+    .target(env_logger::Target::Pipe(Box::new(logfile::LogWriter {
+        file,
+    })))
+    .try_init();
+}
 
-    info!("Starting universal proxy server at http://127.0.0.1:3030");
+// 创建并监听代理服务器，返回服务器和实际监听的地址
+// 需要在 actix 的 System 中调用
+fn http_server(config: &ProxyConfig) -> std::io::Result<(Server, SocketAddr)> {
+    let upstream = web::Data::new(UpstreamClient::new(config)?);
+    let cors_origins = config.cors_origins.clone();
+    let secret = web::Data::new(SharedSecret(config.shared_secret.clone()));
+    if config.shared_secret.is_none() {
+        warn!("No shared secret configured, the proxy accepts requests from any local client");
+    }
 
-    HttpServer::new(|| {
-        let cors = Cors::default()
-            .allowed_origin("http://tauri.localhost")
-            .allowed_origin("http://localhost:1420")
-            .allowed_origin("http://127.0.0.1:1420")
-            // .allowed_origin("file://")
+    let server = HttpServer::new(move || {
+        let cors = cors_origins
+            .iter()
+            .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
             .allowed_methods(vec!["GET", "POST", "OPTIONS"])
             .allowed_headers(vec![
                 "Authorization",
@@ -25,288 +85,121 @@ pub async fn main() -> std::io::Result<()> {
                 "Accept",
                 "Origin",
                 "X-Requested-With",
-                "batchId",
+                SECRET_HEADER,
             ])
+            .expose_headers(vec![UPSTREAM_DATE_HEADER])
             .supports_credentials()
             .max_age(3600);
 
-        App::new().wrap(cors).service(
-            web::resource("/api/proxy/{endpoint:.*}")
-                .route(web::post().to(proxy_handler))
-                .route(web::get().to(proxy_handler_get)),
-        )
+        App::new()
+            .wrap(from_fn(require_secret))
+            .wrap(cors)
+            .app_data(secret.clone())
+            .app_data(upstream.clone())
+            .app_data(
+                web::JsonConfig::default()
+                    .error_handler(|err, _req| ProxyError::InvalidParams(err.to_string()).into()),
+            )
+            .app_data(
+                web::QueryConfig::default()
+                    .error_handler(|err, _req| ProxyError::InvalidParams(err.to_string()).into()),
+            )
+            .service(web::resource("/api/health").route(web::get().to(health)))
+            .service(web::resource("/api/session/reset").route(web::post().to(reset_session)))
+            .service(
+                web::resource("/api/proxy/{operation}")
+                    .route(web::post().to(proxy_handler))
+                    .route(web::get().to(proxy_handler_get)),
+            )
     })
-    .bind("127.0.0.1:3030")?
-    .run()
-    .await
+    .shutdown_timeout(3)
+    .bind(config.addr())?;
+
+    let addr = server
+        .addrs()
+        .first()
+        .copied()
+        .unwrap_or_else(|| config.addr());
+    info!("Starting proxy server at http://{addr}");
+    info!("Forwarding to upstream {}", config.upstream_base);
+
+    Ok((server.run(), addr))
 }
 
-#[derive(Debug, Deserialize)]
-struct ProxyRequest {
-    original_url: String,
-    #[serde(default)]
-    batch_id: Option<String>,
-    #[serde(default)]
-    class_type: Option<String>,
-    #[serde(default)]
-    class_id: Option<String>,
-    #[serde(default)]
-    secret_val: Option<String>,
-    #[serde(default)]
-    params: Option<HashMap<String, String>>,
-    // 新增登录相关字段
-    #[serde(default)]
-    loginname: Option<String>,
-    #[serde(default)]
-    password: Option<String>,
-    #[serde(default)]
-    captcha: Option<String>,
-    #[serde(default)]
-    uuid: Option<String>,
+// 健康检查，ProxyHandle::health_check 使用
+async fn health() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({ "status": "ok" }))
 }
 
-async fn proxy_handler_get(req: HttpRequest, path: web::Path<String>) -> HttpResponse {
-    let endpoint = path.into_inner();
-    let params = req.query_string();
-    debug!("Handling GET proxy request for endpoint: {endpoint}");
-    debug!("Query params: {params}");
-
-    let client = reqwest::Client::builder()
-        .danger_accept_invalid_certs(true)
-        .build()
-        .unwrap_or_default();
-
-    // 获取原始URL
-    let original_url = match endpoint.as_str() {
-        "profile/index.html" => "https://icourses.jlu.edu.cn/xsxk/profile/index.html",
-        "elective/grablessons" => {
-            &format!("https://icourses.jlu.edu.cn/xsxk/elective/grablessons?{params}")
-        }
-        _ => {
-            return HttpResponse::BadRequest().json(json!({
-                "error": "Invalid endpoint for GET request"
-            }));
-        }
-    };
+// 退出登录时清空上游会话 cookie
+async fn reset_session(upstream: web::Data<UpstreamClient>) -> HttpResponse {
+    info!("Resetting upstream session cookies");
+    upstream.reset_session();
+    HttpResponse::NoContent().finish()
+}
 
-    let auth_token = match req.headers().get(actix_web::http::header::AUTHORIZATION) {
-        Some(token) => match token.to_str() {
-            Ok(t) => Some(t.to_string()),
-            Err(e) => {
-                error!("Invalid authorization token: {e}");
-                return HttpResponse::BadRequest().json(json!({
-                    "error": "Invalid authorization token"
-                }));
-            }
-        },
-        None => None,
-    };
+async fn proxy_handler_get(
+    upstream_client: web::Data<UpstreamClient>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    params: web::Query<OperationParams>,
+) -> Result<HttpResponse, ProxyError> {
+    let op = Operation::from_name(&path.into_inner())?;
+    debug!("Handling GET proxy request for operation: {}", op.name());
 
-    let mut headers = HeaderMap::new();
-    if let Some(token) = auth_token {
-        headers.insert(AUTHORIZATION, HeaderValue::from_str(&token).unwrap());
+    if op.method() != Method::Get {
+        return Err(ProxyError::MethodNotAllowed(op.name().to_string()));
     }
 
-    debug!("Sending GET request to: {original_url}");
-
-    match client.get(original_url).headers(headers).send().await {
-        Ok(response) => {
-            let status = response.status();
-            debug!("Received response with status: {status}");
-
-            match response.text().await {
-                Ok(text) => HttpResponse::Ok().content_type("text/html").body(text),
-                Err(e) => {
-                    error!("Failed to get response text: {e}");
-                    HttpResponse::InternalServerError().json(json!({
-                        "error": format!("Failed to read response: {}", e)
-                    }))
-                }
-            }
-        }
-        Err(e) => {
-            error!("Request failed: {e}");
-            HttpResponse::InternalServerError().json(json!({
-                "error": format!("Request failed: {}", e)
-            }))
-        }
-    }
+    let response = upstream_client
+        .execute(op, params.into_inner(), auth_token(&req)?)
+        .await?;
+    Ok(into_http_response(response))
 }
 
 async fn proxy_handler(
-    body: web::Json<ProxyRequest>,
+    upstream_client: web::Data<UpstreamClient>,
     req: HttpRequest,
     path: web::Path<String>,
-) -> HttpResponse {
-    let endpoint = path.into_inner();
-    debug!("Handling proxy request for endpoint: {endpoint}");
-    debug!("Request body: {body:?}");
-
-    let client = reqwest::Client::builder()
-        .danger_accept_invalid_certs(true)
-        .build()
-        .unwrap_or_default();
-
-    let auth_token = match req.headers().get(actix_web::http::header::AUTHORIZATION) {
-        Some(token) => match token.to_str() {
-            Ok(t) => Some(t.to_string()),
-            Err(e) => {
-                error!("Invalid authorization token: {e}");
-                return HttpResponse::BadRequest().json(json!({
-                    "error": "Invalid authorization token"
-                }));
-            }
-        },
-        None => None,
-    };
+    params: web::Json<OperationParams>,
+) -> Result<HttpResponse, ProxyError> {
+    let op = Operation::from_name(&path.into_inner())?;
+    debug!("Handling proxy request for operation: {}", op.name());
+    debug!("Request params: {params:?}");
 
-    let keep_alive = match req.headers().get("Connection") {
-        Some(token) => match token.to_str() {
-            Ok(t) => t.eq_ignore_ascii_case("keep-alive"),
-            Err(e) => {
-                error!("Invalid Connection token: {e}");
-                false
-            }
-        },
-        None => false,
-    };
-
-    let batch_id = match req.headers().get("BatchId") {
-        Some(token) => match token.to_str() {
-            Ok(t) => Some(t.to_string()),
-            Err(e) => {
-                error!("Invalid BatchId token: {e}");
-                return HttpResponse::BadRequest().json(json!({
-                    "error": "Invalid BatchId token"
-                }));
-            }
-        },
-        None => None,
-    };
-
-    let mut headers = HeaderMap::new();
-    // 配置常用headers
-    headers.insert("User-Agent", HeaderValue::from_str("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/139.0.0.0 Safari/537.36").unwrap());
-    // headers.insert("Accept-Encoding", HeaderValue::from_str("gzip, deflate, br").unwrap());
-    // headers.insert("Accept", HeaderValue::from_str("*/*").unwrap());
-    // headers.insert("Content-Type", HeaderValue::from_str("application/json, text/plain, */*").unwrap());
-    // headers.insert("Content-Length", HeaderValue::from_str("0").unwrap());
-    // headers.insert("Connection", HeaderValue::from_str("keep-alive").unwrap());
-    // headers.insert("Host", HeaderValue::from_str("icourses.jlu.edu.cn").unwrap());
-    // headers.insert("host", HeaderValue::from_str("icourses.jlu.edu.cn").unwrap());
-
-    if let Some(token) = auth_token {
-        headers.insert(AUTHORIZATION, HeaderValue::from_str(&token).unwrap());
-    }
-    if keep_alive {
-        headers.insert("Connection", HeaderValue::from_str("keep-alive").unwrap());
-    }
-    if body.original_url.contains("icourses.jlu.edu.cn") {
-        headers.insert(
-            "Origin",
-            HeaderValue::from_str("https://icourses.jlu.edu.cn").unwrap(),
-        );
-    }
-    if body.original_url.contains("xsxk/sc/clazz/list")
-        && let Some(batch_id) = batch_id
-    {
-        headers.insert(
-            "Referer",
-            HeaderValue::from_str(&format!(
-                "https://icourses.jlu.edu.cn/xsxk/profile/index.html?batchId={batch_id}"
-            ))
-            .unwrap(),
-        );
-    }
-
-    // 构建请求体
-    let mut request_body = HashMap::new();
-    if body.original_url.contains("xsxk/elective/user")
-        && let Some(batch_id) = &body.batch_id
-    {
-        request_body.insert("batchId", batch_id);
-    }
-    if let Some(loginname) = &body.loginname {
-        request_body.insert("loginname", loginname);
-    }
-    if let Some(password) = &body.password {
-        request_body.insert("password", password);
-    }
-    if let Some(captcha) = &body.captcha {
-        request_body.insert("captcha", captcha);
-    }
-    if let Some(uuid) = &body.uuid {
-        request_body.insert("uuid", uuid);
+    if op.method() != Method::Post {
+        return Err(ProxyError::MethodNotAllowed(op.name().to_string()));
     }
 
-    // 构建查询参数
-    let mut query_params = HashMap::new();
-    // if let Some(batch_id) = &body.batch_id {
-    //     query_params.insert("batchId", batch_id);
-    // }
-    if let Some(class_type) = &body.class_type {
-        query_params.insert("clazzType", class_type);
-    }
-    if let Some(class_id) = &body.class_id {
-        query_params.insert("clazzId", class_id);
-    }
-    if let Some(secret_val) = &body.secret_val {
-        query_params.insert("secretVal", secret_val);
-    }
-    if let Some(extra_params) = &body.params {
-        query_params.extend(extra_params.iter().map(|(k, v)| (k.as_str(), v)));
-    }
-
-    debug!("Sending request to: {}", body.original_url);
-    debug!("Headers: {headers:?}");
-    debug!("Query params: {query_params:?}");
-    debug!("Request body: {request_body:?}");
-
-    let mut request = client.post(&body.original_url).headers(headers);
-
-    // 添加请求体或查询参数
-    if !request_body.is_empty() {
-        // 应当以urlencoded形式发送
-        let request_body = serde_urlencoded::to_string(&request_body).unwrap_or_default();
-        request = request
-            .header("Content-Type", "application/x-www-form-urlencoded")
-            .body(request_body);
-    }
-    if !query_params.is_empty() {
-        request = request.query(&query_params);
-    }
+    let response = upstream_client
+        .execute(op, params.into_inner(), auth_token(&req)?)
+        .await?;
+    Ok(into_http_response(response))
+}
 
-    match request.send().await {
-        Ok(response) => {
-            let status = response.status();
-            debug!("Received response with status: {status}");
+// 前端携带的上游 token
+fn auth_token(req: &HttpRequest) -> Result<Option<&str>, ProxyError> {
+    req.headers()
+        .get(actix_web::http::header::AUTHORIZATION)
+        .map(|token| {
+            token.to_str().map_err(|e| {
+                error!("Invalid authorization token: {e}");
+                ProxyError::InvalidHeader("Authorization")
+            })
+        })
+        .transpose()
+}
 
-            match response.text().await {
-                Ok(text) => {
-                    debug!("Response text: {text}");
-                    match serde_json::from_str::<serde_json::Value>(&text) {
-                        Ok(json_value) => HttpResponse::Ok()
-                            .content_type("application/json")
-                            .json(json_value),
-                        Err(e) => {
-                            warn!("Failed to parse response as JSON: {e}, returning raw text");
-                            HttpResponse::Ok().content_type("text/plain").body(text)
-                        }
-                    }
-                }
-                Err(e) => {
-                    error!("Failed to get response text: {e}");
-                    HttpResponse::InternalServerError().json(json!({
-                        "error": format!("Failed to read response: {}", e)
-                    }))
-                }
-            }
-        }
-        Err(e) => {
-            error!("Request failed: {e}");
-            HttpResponse::InternalServerError().json(json!({
-                "error": format!("Request failed: {}", e)
-            }))
-        }
+// 上游的状态码和 Date 头原样返回给前端
+fn into_http_response(response: UpstreamResponse) -> HttpResponse {
+    let status = actix_web::http::StatusCode::from_u16(response.status)
+        .unwrap_or(actix_web::http::StatusCode::BAD_GATEWAY);
+    let mut builder = HttpResponse::build(status);
+    if let Some(date) = response.date {
+        builder.insert_header((UPSTREAM_DATE_HEADER, date));
+    }
+    match response.body {
+        UpstreamBody::Json(json_value) => builder.content_type("application/json").json(json_value),
+        UpstreamBody::Text(text) => builder.content_type("text/html").body(text),
     }
 }
@@ -0,0 +1,165 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// 单个日志文件默认的大小上限
+pub const MAX_BYTES: u64 = 2 * 1024 * 1024;
+// 保留的历史文件数量，<文件名>.1 为最近的一个
+const KEEP: usize = 3;
+
+// 按大小滚动的日志文件
+pub struct RotatingFile {
+    path: PathBuf,
+    // 滚动时先关闭当前文件，Windows 上无法重命名已打开的文件
+    file: Option<File>,
+    written: u64,
+    // 超过后滚动
+    max_bytes: u64,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, max_bytes: u64) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = Self {
+            path: path.to_path_buf(),
+            file: None,
+            written: 0,
+            max_bytes,
+        };
+        file.reopen()?;
+        Ok(file)
+    }
+
+    fn reopen(&mut self) -> io::Result<&mut File> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.written = file.metadata()?.len();
+        Ok(self.file.insert(file))
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{index}"));
+        PathBuf::from(name)
+    }
+
+    // 关闭当前文件后依次重命名，下次写入时重新创建
+    fn rotate(&mut self) -> io::Result<()> {
+        drop(self.file.take());
+        for index in (1..KEEP).rev() {
+            let from = self.rotated(index);
+            if from.exists() {
+                fs::rename(&from, self.rotated(index + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated(1))
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // 滚动失败（例如文件被其他程序占用）时继续追加到当前文件
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            let _ = self.rotate();
+        }
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => self.reopen()?,
+        };
+        let n = file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+// 日志同时写到标准错误和日志文件
+pub struct LogWriter {
+    pub file: Option<RotatingFile>,
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let _ = io::stderr().write_all(buf);
+        if let Some(file) = &mut self.file {
+            // 日志文件写入失败时不影响代理本身
+            let _ = file.write_all(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let _ = io::stderr().flush();
+        if let Some(file) = &mut self.file {
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(path: &Path) -> Option<String> {
+        fs::read_to_string(path).ok()
+    }
+
+    #[test]
+    fn rotates_and_keeps_the_latest_files() {
+        let dir = std::env::temp_dir().join(format!("funky-logfile-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("proxy.log");
+        let mut file = RotatingFile::open(&path, 10).unwrap();
+
+        // 每行 8 字节，上限 10 字节，之后的每次写入都会先滚动
+        file.write_all(b"aaaaaaa\n").unwrap();
+        file.write_all(b"bbbbbbb\n").unwrap();
+        file.write_all(b"ccccccc\n").unwrap();
+        assert_eq!(read(&path).as_deref(), Some("ccccccc\n"));
+        assert_eq!(read(&file.rotated(1)).as_deref(), Some("bbbbbbb\n"));
+        assert_eq!(read(&file.rotated(2)).as_deref(), Some("aaaaaaa\n"));
+        assert!(!file.rotated(3).exists());
+
+        // 超过保留数量后丢弃最旧的文件
+        file.write_all(b"ddddddd\n").unwrap();
+        file.write_all(b"eeeeeee\n").unwrap();
+        assert_eq!(read(&path).as_deref(), Some("eeeeeee\n"));
+        assert_eq!(read(&file.rotated(1)).as_deref(), Some("ddddddd\n"));
+        assert_eq!(read(&file.rotated(2)).as_deref(), Some("ccccccc\n"));
+        assert_eq!(read(&file.rotated(3)).as_deref(), Some("bbbbbbb\n"));
+        assert!(!file.rotated(4).exists());
+
+        drop(file);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reopening_continues_from_the_existing_size() {
+        let dir = std::env::temp_dir().join(format!("funky-logfile-reopen-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("proxy.log");
+        RotatingFile::open(&path, 10)
+            .unwrap()
+            .write_all(b"aaaaaaa\n")
+            .unwrap();
+
+        let mut file = RotatingFile::open(&path, 10).unwrap();
+        file.write_all(b"bbbbbbb\n").unwrap();
+        assert_eq!(read(&path).as_deref(), Some("bbbbbbb\n"));
+        assert_eq!(read(&file.rotated(1)).as_deref(), Some("aaaaaaa\n"));
+
+        drop(file);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,503 @@
+use crate::error::ProxyError;
+use crate::redact;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::fmt;
+
+// 代理对外暴露的具名操作，每个操作对应一个固定的上游路径
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Captcha,
+    Login,
+    ProfilePage,
+    SetBatch,
+    SelectedList,
+    FavoriteList,
+    FavoriteAdd,
+    FavoriteRemove,
+    ClassList,
+    AddCourse,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Post,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 10] = [
+        Operation::Captcha,
+        Operation::Login,
+        Operation::ProfilePage,
+        Operation::SetBatch,
+        Operation::SelectedList,
+        Operation::FavoriteList,
+        Operation::FavoriteAdd,
+        Operation::FavoriteRemove,
+        Operation::ClassList,
+        Operation::AddCourse,
+    ];
+
+    pub fn from_name(name: &str) -> Result<Self, ProxyError> {
+        Self::ALL
+            .into_iter()
+            .find(|op| op.name() == name)
+            .ok_or_else(|| ProxyError::UnknownOperation(name.to_string()))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Operation::Captcha => "captcha",
+            Operation::Login => "login",
+            Operation::ProfilePage => "profile_page",
+            Operation::SetBatch => "set_batch",
+            Operation::SelectedList => "selected_list",
+            Operation::FavoriteList => "favorite_list",
+            Operation::FavoriteAdd => "favorite_add",
+            Operation::FavoriteRemove => "favorite_remove",
+            Operation::ClassList => "class_list",
+            Operation::AddCourse => "add_course",
+        }
+    }
+
+    pub fn path(self) -> &'static str {
+        match self {
+            Operation::Captcha => "/xsxk/auth/captcha",
+            Operation::Login => "/xsxk/auth/login",
+            Operation::ProfilePage => "/xsxk/profile/index.html",
+            Operation::SetBatch => "/xsxk/elective/user",
+            Operation::SelectedList => "/xsxk/elective/select",
+            Operation::FavoriteList => "/xsxk/sc/clazz/list",
+            Operation::FavoriteAdd => "/xsxk/sc/clazz/add",
+            Operation::FavoriteRemove => "/xsxk/sc/clazz/del",
+            Operation::ClassList => "/xsxk/elective/clazz/list",
+            Operation::AddCourse => "/xsxk/sc/clazz/addxk",
+        }
+    }
+
+    pub fn method(self) -> Method {
+        match self {
+            Operation::ProfilePage => Method::Get,
+            _ => Method::Post,
+        }
+    }
+
+    // 必填参数
+    fn required(self) -> &'static [&'static str] {
+        match self {
+            Operation::Captcha | Operation::ProfilePage => &[],
+            Operation::Login => &["loginname", "password", "captcha", "uuid"],
+            Operation::SetBatch | Operation::SelectedList | Operation::FavoriteList => {
+                &["batch_id"]
+            }
+            Operation::ClassList => &["batch_id", "class_type"],
+            Operation::AddCourse | Operation::FavoriteAdd => {
+                &["batch_id", "class_type", "class_id", "secret_val"]
+            }
+            Operation::FavoriteRemove => &["batch_id", "class_type", "class_id"],
+        }
+    }
+
+    // 允许出现的参数（包含必填参数）
+    fn allowed(self) -> &'static [&'static str] {
+        match self {
+            Operation::ProfilePage => &["batch_id"],
+            Operation::ClassList => &[
+                "batch_id",
+                "class_type",
+                "page_number",
+                "page_size",
+                "keyword",
+            ],
+            op => op.required(),
+        }
+    }
+}
+
+// 前端传入的参数，不允许出现未知字段（例如 original_url）
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OperationParams {
+    #[serde(default)]
+    pub loginname: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub captcha: Option<String>,
+    #[serde(default)]
+    pub uuid: Option<String>,
+    #[serde(default)]
+    pub batch_id: Option<String>,
+    #[serde(default)]
+    pub class_type: Option<String>,
+    #[serde(default)]
+    pub class_id: Option<String>,
+    #[serde(default)]
+    pub secret_val: Option<String>,
+    #[serde(default)]
+    pub page_number: Option<u32>,
+    #[serde(default)]
+    pub page_size: Option<u32>,
+    #[serde(default)]
+    pub keyword: Option<String>,
+}
+
+impl OperationParams {
+    fn strings(&self) -> [(&'static str, Option<&str>); 9] {
+        [
+            ("loginname", self.loginname.as_deref()),
+            ("password", self.password.as_deref()),
+            ("captcha", self.captcha.as_deref()),
+            ("uuid", self.uuid.as_deref()),
+            ("batch_id", self.batch_id.as_deref()),
+            ("class_type", self.class_type.as_deref()),
+            ("class_id", self.class_id.as_deref()),
+            ("secret_val", self.secret_val.as_deref()),
+            ("keyword", self.keyword.as_deref()),
+        ]
+    }
+
+    fn present(&self) -> Vec<&'static str> {
+        let mut present: Vec<&'static str> = self
+            .strings()
+            .into_iter()
+            .filter_map(|(name, value)| value.map(|_| name))
+            .collect();
+        if self.page_number.is_some() {
+            present.push("page_number");
+        }
+        if self.page_size.is_some() {
+            present.push("page_size");
+        }
+        present
+    }
+
+    pub fn validate(&self, op: Operation) -> Result<(), ProxyError> {
+        for name in self.present() {
+            if !op.allowed().contains(&name) {
+                return Err(ProxyError::InvalidParams(format!(
+                    "`{name}` is not accepted by `{}`",
+                    op.name()
+                )));
+            }
+        }
+
+        let strings = self.strings();
+        for name in op.required() {
+            let value = strings
+                .iter()
+                .find(|(n, _)| n == name)
+                .and_then(|(_, v)| *v);
+            if value.is_none_or(str::is_empty) {
+                return Err(ProxyError::InvalidParams(format!(
+                    "`{name}` is required by `{}`",
+                    op.name()
+                )));
+            }
+        }
+
+        for (name, value) in strings {
+            let Some(value) = value else { continue };
+            if value.len() > 1024 || value.chars().any(char::is_control) {
+                return Err(ProxyError::InvalidParams(format!("`{name}` is malformed")));
+            }
+            // 这几个值会被放进请求头或查询参数，只允许常见的标识符字符
+            let is_identifier = matches!(name, "uuid" | "batch_id" | "class_type" | "class_id");
+            if is_identifier
+                && !value
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(ProxyError::InvalidParams(format!("`{name}` is malformed")));
+            }
+        }
+
+        if let Some(page_size) = self.page_size
+            && !(1..=100).contains(&page_size)
+        {
+            return Err(ProxyError::InvalidParams(
+                "`page_size` must be between 1 and 100".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+// 调试输出中隐藏密码、验证码等敏感参数
+impl fmt::Debug for OperationParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("OperationParams");
+        for (name, value) in self.strings() {
+            if let Some(value) = value {
+                s.field(name, &redact::value(name, value));
+            }
+        }
+        if let Some(page_number) = self.page_number {
+            s.field("page_number", &page_number);
+        }
+        if let Some(page_size) = self.page_size {
+            s.field("page_size", &page_size);
+        }
+        s.finish()
+    }
+}
+
+// 由操作和参数构造出的上游请求
+pub struct UpstreamRequest {
+    pub method: Method,
+    pub url: String,
+    pub batch_id: Option<String>,
+    pub form: Vec<(&'static str, String)>,
+    pub query: Vec<(&'static str, String)>,
+    pub json: Option<Value>,
+}
+
+impl fmt::Debug for UpstreamRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UpstreamRequest")
+            .field("method", &self.method)
+            .field("url", &self.url)
+            .field("batch_id", &self.batch_id)
+            .field("form", &redact::pairs(&self.form))
+            .field("query", &redact::pairs(&self.query))
+            .field("json", &self.json.as_ref().map(redact::json))
+            .finish()
+    }
+}
+
+impl UpstreamRequest {
+    pub fn build(op: Operation, params: OperationParams, base: &str) -> Result<Self, ProxyError> {
+        params.validate(op)?;
+
+        let mut request = UpstreamRequest {
+            method: op.method(),
+            url: format!("{base}{}", op.path()),
+            batch_id: params.batch_id.clone(),
+            form: Vec::new(),
+            query: Vec::new(),
+            json: None,
+        };
+
+        match op {
+            Operation::Captcha | Operation::SelectedList | Operation::FavoriteList => {}
+            Operation::ProfilePage => {
+                if let Some(batch_id) = params.batch_id {
+                    request.query.push(("batchId", batch_id));
+                }
+            }
+            Operation::Login => {
+                request.form = vec![
+                    ("loginname", params.loginname.unwrap_or_default()),
+                    ("password", params.password.unwrap_or_default()),
+                    ("captcha", params.captcha.unwrap_or_default()),
+                    ("uuid", params.uuid.unwrap_or_default()),
+                ];
+            }
+            Operation::SetBatch => {
+                request.form = vec![("batchId", params.batch_id.unwrap_or_default())];
+            }
+            Operation::ClassList => {
+                request.json = Some(json!({
+                    "teachingClassType": params.class_type.unwrap_or_default(),
+                    "pageNumber": params.page_number.unwrap_or(1),
+                    "pageSize": params.page_size.unwrap_or(10),
+                    "orderBy": "",
+                    "KEY": params.keyword.unwrap_or_default(),
+                }));
+            }
+            Operation::AddCourse | Operation::FavoriteAdd => {
+                request.query = vec![
+                    ("clazzType", params.class_type.unwrap_or_default()),
+                    ("clazzId", params.class_id.unwrap_or_default()),
+                    ("secretVal", params.secret_val.unwrap_or_default()),
+                ];
+            }
+            Operation::FavoriteRemove => {
+                request.query = vec![
+                    ("clazzType", params.class_type.unwrap_or_default()),
+                    ("clazzId", params.class_id.unwrap_or_default()),
+                ];
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(value: Value) -> OperationParams {
+        serde_json::from_value(value).unwrap()
+    }
+
+    // 只填写必填参数
+    fn required_params(op: Operation) -> serde_json::Map<String, Value> {
+        op.required()
+            .iter()
+            .map(|name| (name.to_string(), json!("A1")))
+            .collect()
+    }
+
+    fn assert_invalid(result: Result<(), ProxyError>) {
+        assert!(
+            matches!(result, Err(ProxyError::InvalidParams(_))),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn only_known_operations_are_accepted() {
+        for op in Operation::ALL {
+            assert_eq!(Operation::from_name(op.name()).unwrap(), op);
+        }
+        for name in ["", "proxy", "ADD_COURSE", "/xsxk/auth/login"] {
+            assert!(matches!(
+                Operation::from_name(name),
+                Err(ProxyError::UnknownOperation(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        for field in ["original_url", "url", "path", "headers", "batchId"] {
+            let value = json!({ "batch_id": "B1", field: "https://example.com" });
+            assert!(serde_json::from_value::<OperationParams>(value).is_err());
+        }
+    }
+
+    #[test]
+    fn required_params_must_be_present_and_non_empty() {
+        for op in Operation::ALL {
+            let required = required_params(op);
+            assert!(params(Value::Object(required.clone())).validate(op).is_ok());
+            for name in op.required() {
+                let mut missing = required.clone();
+                missing.remove(*name);
+                assert_invalid(params(Value::Object(missing)).validate(op));
+
+                let mut empty = required.clone();
+                empty.insert(name.to_string(), json!(""));
+                assert_invalid(params(Value::Object(empty)).validate(op));
+            }
+        }
+    }
+
+    #[test]
+    fn params_outside_the_allowlist_are_rejected() {
+        let names = [
+            "loginname",
+            "password",
+            "captcha",
+            "uuid",
+            "batch_id",
+            "class_type",
+            "class_id",
+            "secret_val",
+            "keyword",
+        ];
+        for op in Operation::ALL {
+            for name in names {
+                let mut value = required_params(op);
+                value.insert(name.to_string(), json!("A1"));
+                let result = params(Value::Object(value)).validate(op);
+                if op.allowed().contains(&name) {
+                    assert!(result.is_ok(), "{} {name}", op.name());
+                } else {
+                    assert_invalid(result);
+                }
+            }
+            let mut value = required_params(op);
+            value.insert("page_size".to_string(), json!(10));
+            let result = params(Value::Object(value)).validate(op);
+            assert_eq!(result.is_ok(), op == Operation::ClassList, "{}", op.name());
+        }
+    }
+
+    #[test]
+    fn identifiers_only_accept_safe_characters() {
+        let op = Operation::FavoriteRemove;
+        for name in ["batch_id", "class_type", "class_id"] {
+            for bad in ["a b", "a/b", "../a", "a%2F", "a;b", "a\r\nb", "课程"] {
+                let mut value = required_params(op);
+                value.insert(name.to_string(), json!(bad));
+                assert_invalid(params(Value::Object(value)).validate(op));
+            }
+        }
+        let value =
+            json!({ "batch_id": "2024-2025_1", "class_type": "TJKC", "class_id": "ABC123" });
+        assert!(params(value).validate(op).is_ok());
+    }
+
+    #[test]
+    fn free_text_rejects_control_characters_and_long_values() {
+        let mut value = required_params(Operation::Login);
+        value.insert("password".to_string(), json!("密码 with spaces"));
+        assert!(
+            params(Value::Object(value.clone()))
+                .validate(Operation::Login)
+                .is_ok()
+        );
+
+        value.insert("password".to_string(), json!("line\nbreak"));
+        assert_invalid(params(Value::Object(value.clone())).validate(Operation::Login));
+
+        value.insert("password".to_string(), json!("a".repeat(1025)));
+        assert_invalid(params(Value::Object(value)).validate(Operation::Login));
+    }
+
+    #[test]
+    fn page_size_must_be_between_1_and_100() {
+        for (page_size, valid) in [(0, false), (1, true), (100, true), (101, false)] {
+            let value = json!({ "batch_id": "B1", "class_type": "TJKC", "page_size": page_size });
+            let result = params(value).validate(Operation::ClassList);
+            assert_eq!(result.is_ok(), valid, "page_size {page_size}");
+        }
+    }
+
+    #[test]
+    fn builds_fixed_upstream_requests() {
+        let base = "https://xk.example.com";
+        let value = json!({
+            "batch_id": "B1",
+            "class_type": "TJKC",
+            "class_id": "C1",
+            "secret_val": "s/v+=",
+        });
+        let request = UpstreamRequest::build(Operation::AddCourse, params(value), base).unwrap();
+        assert_eq!(request.method, Method::Post);
+        assert_eq!(request.url, "https://xk.example.com/xsxk/sc/clazz/addxk");
+        assert_eq!(request.batch_id.as_deref(), Some("B1"));
+        assert_eq!(
+            request.query,
+            [
+                ("clazzType", "TJKC".to_string()),
+                ("clazzId", "C1".to_string()),
+                ("secretVal", "s/v+=".to_string()),
+            ]
+        );
+
+        let value = json!({ "batch_id": "B1", "class_type": "TJKC", "keyword": "数学" });
+        let request = UpstreamRequest::build(Operation::ClassList, params(value), base).unwrap();
+        assert_eq!(
+            request.json,
+            Some(json!({
+                "teachingClassType": "TJKC",
+                "pageNumber": 1,
+                "pageSize": 10,
+                "orderBy": "",
+                "KEY": "数学",
+            }))
+        );
+
+        let request =
+            UpstreamRequest::build(Operation::ProfilePage, OperationParams::default(), base)
+                .unwrap();
+        assert_eq!(request.method, Method::Get);
+        assert!(request.query.is_empty());
+    }
+}
@@ -0,0 +1,205 @@
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+
+// 日志中替换敏感值使用的占位符
+pub const MASK: &str = "***";
+
+// 日志中需要隐藏的字段，包括请求参数、请求头和上游响应中的字段，比较时忽略大小写
+const SENSITIVE: &[&str] = &[
+    "password",
+    "captcha",
+    "uuid",
+    "token",
+    "authorization",
+    "cookie",
+    "set-cookie",
+    "secret_val",
+    "secretval",
+    "shared_secret",
+    "x-funky-secret",
+    "loginname",
+    "xh",
+    "xm",
+];
+
+// 响应体在日志中最多保留的字符数
+const MAX_BODY_CHARS: usize = 1000;
+
+pub fn is_sensitive(name: &str) -> bool {
+    SENSITIVE.iter().any(|s| s.eq_ignore_ascii_case(name))
+}
+
+pub fn value<'a>(name: &str, value: &'a str) -> &'a str {
+    if is_sensitive(name) { MASK } else { value }
+}
+
+// 表单或查询参数
+pub fn pairs<'a>(pairs: &'a [(&'static str, String)]) -> Vec<(&'static str, &'a str)> {
+    pairs
+        .iter()
+        .map(|(name, v)| (*name, value(name, v)))
+        .collect()
+}
+
+pub fn headers(headers: &HeaderMap) -> Vec<(&str, &str)> {
+    headers
+        .iter()
+        .map(|(name, v)| {
+            let name = name.as_str();
+            (name, value(name, v.to_str().unwrap_or("<binary>")))
+        })
+        .collect()
+}
+
+// 递归隐藏 JSON 中的敏感字段
+pub fn json(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, v)| {
+                    let v = if is_sensitive(key) {
+                        Value::String(MASK.to_string())
+                    } else {
+                        json(v)
+                    };
+                    (key.clone(), v)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(json).collect()),
+        other => other.clone(),
+    }
+}
+
+// 上游响应体，JSON 隐藏敏感字段后截断，其他内容只记录长度
+pub fn body(text: &str) -> String {
+    match serde_json::from_str::<Value>(text) {
+        Ok(value) => {
+            let text = json(&value).to_string();
+            if text.chars().count() > MAX_BODY_CHARS {
+                let head: String = text.chars().take(MAX_BODY_CHARS).collect();
+                format!("{head}...")
+            } else {
+                text
+            }
+        }
+        Err(_) => format!("<{} bytes>", text.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::{Method, OperationParams, UpstreamRequest};
+    use reqwest::header::HeaderValue;
+    use serde_json::json;
+
+    #[test]
+    fn masks_sensitive_query_and_form_params() {
+        let params = [
+            ("password", "hunter2".to_string()),
+            ("secretVal", "s3cret".to_string()),
+            ("token", "abc".to_string()),
+            ("clazzId", "C1".to_string()),
+        ];
+        assert_eq!(
+            pairs(&params),
+            [
+                ("password", MASK),
+                ("secretVal", MASK),
+                ("token", MASK),
+                ("clazzId", "C1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn masks_sensitive_headers() {
+        let mut map = HeaderMap::new();
+        map.insert("Cookie", HeaderValue::from_static("JSESSIONID=abc"));
+        map.insert("Authorization", HeaderValue::from_static("Bearer abc"));
+        map.insert("X-Funky-Secret", HeaderValue::from_static("shared"));
+        map.insert("token", HeaderValue::from_static("abc"));
+        map.insert("batchId", HeaderValue::from_static("B1"));
+        let mut redacted = headers(&map);
+        redacted.sort();
+        assert_eq!(
+            redacted,
+            [
+                ("authorization", MASK),
+                ("batchid", "B1"),
+                ("cookie", MASK),
+                ("token", MASK),
+                ("x-funky-secret", MASK),
+            ]
+        );
+    }
+
+    #[test]
+    fn masks_nested_json_fields() {
+        let value = json!({
+            "code": 200,
+            "data": {
+                "token": "abc",
+                "student": { "XH": "2024001", "XM": "张三", "college": "计算机学院" },
+                "rows": [{ "JXBID": "C1", "secretVal": "s3cret" }],
+            },
+            "Set-Cookie": ["a=1", "b=2"],
+        });
+        assert_eq!(
+            json(&value),
+            json!({
+                "code": 200,
+                "data": {
+                    "token": MASK,
+                    "student": { "XH": MASK, "XM": MASK, "college": "计算机学院" },
+                    "rows": [{ "JXBID": "C1", "secretVal": MASK }],
+                },
+                "Set-Cookie": MASK,
+            })
+        );
+    }
+
+    #[test]
+    fn masks_and_truncates_response_bodies() {
+        assert_eq!(
+            body(r#"{"data":{"token":"abc"}}"#),
+            format!(r#"{{"data":{{"token":"{MASK}"}}}}"#)
+        );
+        assert_eq!(body("<html>token=abc</html>"), "<22 bytes>");
+
+        let long = json!({ "msg": "很".repeat(2000) }).to_string();
+        let logged = body(&long);
+        assert!(logged.ends_with("..."));
+        assert_eq!(logged.chars().count(), MAX_BODY_CHARS + 3);
+    }
+
+    #[test]
+    fn debug_output_hides_sensitive_params() {
+        let params: OperationParams = serde_json::from_value(json!({
+            "loginname": "2024001",
+            "password": "hunter2",
+            "captcha": "x7k2",
+            "uuid": "U1",
+        }))
+        .unwrap();
+        let logged = format!("{params:?}");
+        for secret in ["2024001", "hunter2", "x7k2", "U1"] {
+            assert!(!logged.contains(secret), "{logged}");
+        }
+
+        let request = UpstreamRequest {
+            method: Method::Post,
+            url: "https://xk.example.com/xsxk/sc/clazz/addxk".to_string(),
+            batch_id: Some("B1".to_string()),
+            form: vec![("password", "hunter2".to_string())],
+            query: vec![("secretVal", "s3cret".to_string())],
+            json: Some(json!({ "data": { "token": "abc" } })),
+        };
+        let logged = format!("{request:?}");
+        for secret in ["hunter2", "s3cret", "abc"] {
+            assert!(!logged.contains(secret), "{logged}");
+        }
+        assert!(logged.contains("B1"));
+    }
+}
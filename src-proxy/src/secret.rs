@@ -0,0 +1,122 @@
+use crate::error::ProxyError;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{Error, ResponseError, web};
+use log::warn;
+
+// 前端携带共享密钥使用的请求头
+pub const SECRET_HEADER: &str = "X-Funky-Secret";
+
+// 本次启动的共享密钥，未配置时不做校验
+#[derive(Clone)]
+pub struct SharedSecret(pub Option<String>);
+
+// 拒绝没有携带正确密钥的请求，CORS 预检由外层的 Cors 处理
+pub async fn require_secret(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let expected = req
+        .app_data::<web::Data<SharedSecret>>()
+        .and_then(|secret| secret.0.clone());
+
+    if let Some(expected) = expected {
+        let provided = req
+            .headers()
+            .get(SECRET_HEADER)
+            .map(|value| value.as_bytes())
+            .unwrap_or_default();
+        if !constant_time_eq(provided, expected.as_bytes()) {
+            warn!("Rejected request without a valid secret: {}", req.path());
+            // 直接返回响应而不是 Err，这样外层的 Cors 仍会附加跨域头，前端能读到错误信息
+            let response = ProxyError::Unauthorized.error_response();
+            return Ok(req.into_response(response).map_into_right_body());
+        }
+    }
+
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
+
+// 逐字节比较，耗时与内容无关
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{App, HttpResponse};
+    use serde_json::{Value, json};
+
+    // 只有一个 /api/health 路由的应用，secret 为本次启动的共享密钥
+    async fn send(secret: Option<&str>, header: Option<&str>) -> (StatusCode, Value) {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(SharedSecret(secret.map(str::to_string))))
+                .wrap(from_fn(require_secret))
+                .route(
+                    "/api/health",
+                    web::get().to(|| async { HttpResponse::Ok().json(json!({ "status": "ok" })) }),
+                ),
+        )
+        .await;
+        let mut req = TestRequest::get().uri("/api/health");
+        if let Some(header) = header {
+            req = req.insert_header((SECRET_HEADER, header));
+        }
+        let resp = test::call_service(&app, req.to_request()).await;
+        let status = resp.status();
+        let body = test::read_body(resp).await;
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[actix_web::test]
+    async fn missing_secret_is_rejected_with_envelope() {
+        let (status, body) = send(Some("s3cret"), None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body, ProxyError::Unauthorized.envelope());
+    }
+
+    #[actix_web::test]
+    async fn wrong_secret_is_rejected() {
+        for header in ["", "s3crex", "s3cret2"] {
+            let (status, body) = send(Some("s3cret"), Some(header)).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert_eq!(body["error"]["kind"], "unauthorized");
+        }
+    }
+
+    #[actix_web::test]
+    async fn matching_secret_passes() {
+        let (status, body) = send(Some("s3cret"), Some("s3cret")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ok");
+    }
+
+    #[actix_web::test]
+    async fn no_configured_secret_passes() {
+        for header in [None, Some("anything")] {
+            let (status, _) = send(None, header).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+    }
+
+    #[test]
+    fn constant_time_eq_compares_length_and_content() {
+        assert!(constant_time_eq(b"s3cret", b"s3cret"));
+        assert!(constant_time_eq(b"", b""));
+        assert!(!constant_time_eq(b"s3cret", b"s3cre"));
+        assert!(!constant_time_eq(b"s3cre", b"s3cret"));
+        assert!(!constant_time_eq(b"", b"s3cret"));
+        assert!(!constant_time_eq(b"s3cret", b"s3crex"));
+    }
+}
@@ -0,0 +1,168 @@
+use crate::config::ProxyConfig;
+use crate::secret::SECRET_HEADER;
+use actix_web::dev::ServerHandle;
+use log::{error, info};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+// 在独立线程中运行的代理服务器
+// ProxyServer::new(config).port(0).shared_secret(secret).start() 返回运行中的句柄
+pub struct ProxyServer {
+    config: ProxyConfig,
+}
+
+impl ProxyServer {
+    pub fn new(config: ProxyConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn bind(mut self, bind: IpAddr) -> Self {
+        self.config.bind = bind;
+        self
+    }
+
+    // 端口为 0 时由系统分配，实际端口通过 ProxyHandle::addr 获取
+    pub fn port(mut self, port: u16) -> Self {
+        self.config.port = port;
+        self
+    }
+
+    pub fn shared_secret(mut self, secret: impl Into<String>) -> Self {
+        self.config.shared_secret = Some(secret.into());
+        self
+    }
+
+    // 启动服务器，监听成功后返回；配置错误或端口被占用时返回错误
+    pub fn start(mut self) -> io::Result<ProxyHandle> {
+        self.config.normalize();
+        self.config.validate()?;
+        crate::init_logger(&self.config);
+
+        let secret = self.config.shared_secret.clone();
+        let (tx, rx) = mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("funky-proxy".to_string())
+            .spawn(move || {
+                actix_web::rt::System::new().block_on(async move {
+                    let (server, addr) = match crate::http_server(&self.config) {
+                        Ok(server) => server,
+                        Err(e) => {
+                            let _ = tx.send(Err(e));
+                            return;
+                        }
+                    };
+                    let _ = tx.send(Ok((addr, server.handle())));
+                    if let Err(e) = server.await {
+                        error!("Proxy server exited with error: {e}");
+                    }
+                    info!("Proxy server stopped");
+                });
+            })?;
+
+        let (addr, server) = rx
+            .recv()
+            .map_err(|_| io::Error::other("proxy server thread exited before binding"))??;
+
+        Ok(ProxyHandle {
+            addr,
+            secret,
+            server,
+            thread: Arc::new(Mutex::new(Some(thread))),
+        })
+    }
+}
+
+// 运行中的代理服务器
+#[derive(Clone)]
+pub struct ProxyHandle {
+    addr: SocketAddr,
+    secret: Option<String>,
+    server: ServerHandle,
+    thread: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl ProxyHandle {
+    // 实际监听的地址
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    // 请求 /api/health，确认代理能够正常响应
+    pub async fn health_check(&self) -> io::Result<()> {
+        let client = reqwest::Client::builder()
+            .no_proxy()
+            .timeout(Duration::from_secs(3))
+            .build()
+            .map_err(io::Error::other)?;
+
+        let mut request = client.get(format!("{}/api/health", self.url()));
+        if let Some(secret) = &self.secret {
+            request = request.header(SECRET_HEADER, secret);
+        }
+        let response = request.send().await.map_err(io::Error::other)?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!(
+                "health check returned HTTP {}",
+                response.status()
+            )))
+        }
+    }
+
+    // 停止接受新连接，等待进行中的请求完成
+    pub async fn stop(&self) {
+        self.server.stop(true).await;
+    }
+
+    // 同步版本的 stop，等待服务器线程退出，用于应用退出时
+    pub fn shutdown(&self) {
+        // 停止命令在调用 stop 时就已发出，这里不需要等待返回的 future
+        drop(self.server.stop(true));
+        let thread = self.thread.lock().ok().and_then(|mut thread| thread.take());
+        if let Some(thread) = thread {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[actix_web::test]
+    async fn starts_on_a_free_port_and_shuts_down() {
+        let handle = ProxyServer::new(ProxyConfig::default())
+            .port(0)
+            .shared_secret("s3cret")
+            .start()
+            .unwrap();
+        let addr = handle.addr();
+        assert_eq!(addr.ip(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert_ne!(addr.port(), 0);
+        assert_eq!(handle.url(), format!("http://127.0.0.1:{}", addr.port()));
+
+        handle.health_check().await.unwrap();
+        let body: serde_json::Value = reqwest::Client::new()
+            .get(format!("{}/api/health", handle.url()))
+            .header(SECRET_HEADER, "s3cret")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(body["status"], "ok");
+
+        handle.shutdown();
+        assert!(handle.health_check().await.is_err());
+    }
+}
@@ -3,20 +3,347 @@
 // #[cfg_attr(mobile, tauri::mobile_entry_point)]
 // #[tokio::main]
 
-#[cfg(not(mobile))]
-#[tokio::main]
-pub async fn run() {
-    tokio::spawn(async move {
-        let _ = tokio::task::spawn_blocking(|| {
-            let _ = funky_lesson_proxy::main();
+use funky_lesson_proxy::{
+    Operation, OperationParams, ProxyConfig, ProxyError, ProxyHandle, ProxyServer, UpstreamBody,
+    UpstreamClient,
+};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Instant;
+use tauri::Manager;
+use tauri_plugin_dialog::DialogExt;
+use tokio::sync::oneshot;
+
+// 设置为 1 时额外启动 HTTP 代理，方便在浏览器中调试前端
+const HTTP_PROXY_ENV: &str = "FUNKY_LESSON_HTTP_PROXY";
+// 设置为 0 时不写日志文件
+const LOG_FILE_ENV: &str = "FUNKY_LESSON_LOG_FILE";
+// 开发时在浏览器中打开前端的地址，与 tauri.conf.json 的 devUrl 相同
+const FRONTEND_DEV_URL: &str = "http://localhost:1420";
+
+// Tauri 窗口内的前端通过 IPC 调用上游，不再经过本地端口
+struct Backend {
+    client: Result<UpstreamClient, String>,
+    // 日志文件路径，反馈问题时附上
+    log_file: Option<PathBuf>,
+    // 要求启动的 HTTP 代理没有启动成功的原因
+    http_error: Option<String>,
+    requests: Mutex<Requests>,
+}
+
+// 进行中的请求，按前端分配的 request_id 保存，丢弃 Sender 即取消请求
+// 前端的 request_id 递增，proxy_cancel 可能先于 proxy_call 登记请求到达，
+// 此时 id 比登记过的都大，留下 Cancelled，proxy_call 登记时看到后直接返回
+#[derive(Default)]
+struct Requests {
+    entries: HashMap<u64, Request>,
+    // 登记过的最大 request_id
+    last_id: Option<u64>,
+}
+
+enum Request {
+    Pending(oneshot::Sender<()>),
+    Cancelled,
+}
+
+impl Requests {
+    // 登记请求，已经取消时返回 None
+    fn register(&mut self, id: u64) -> Option<oneshot::Receiver<()>> {
+        if let Some(Request::Cancelled) = self.entries.remove(&id) {
+            return None;
+        }
+        self.last_id = self.last_id.max(Some(id));
+        let (tx, rx) = oneshot::channel();
+        self.entries.insert(id, Request::Pending(tx));
+        Some(rx)
+    }
+
+    fn finish(&mut self, id: u64) {
+        self.entries.remove(&id);
+    }
+
+    // 请求已经结束时不做任何事，不会留下无人清理的 Cancelled
+    fn cancel(&mut self, id: u64) {
+        match self.entries.get_mut(&id) {
+            // 丢弃 Sender，进行中的请求随之取消
+            Some(request) => *request = Request::Cancelled,
+            None if self.last_id.is_none_or(|last| id > last) => {
+                self.entries.insert(id, Request::Cancelled);
+            }
+            None => {}
+        }
+    }
+}
+
+// 额外启动的 HTTP 代理，退出时关闭
+struct HttpProxy(Option<ProxyHandle>);
+
+// proxy_call 的结果，date 为上游响应的 Date 头
+// 不通过 Channel 推送进度：Channel 消息可能在命令返回后才到达，耗时和 Date 头随结果一起返回更可靠
+#[derive(Serialize)]
+struct CallResult {
+    status: u16,
+    elapsed_ms: u64,
+    date: Option<String>,
+    body: Value,
+}
+
+impl Backend {
+    fn new(
+        client: Result<UpstreamClient, String>,
+        log_file: Option<PathBuf>,
+        http_error: Option<String>,
+    ) -> Self {
+        Self {
+            client,
+            log_file,
+            http_error,
+            requests: Mutex::default(),
+        }
+    }
+
+    fn requests(&self) -> std::sync::MutexGuard<'_, Requests> {
+        self.requests.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn client(&self) -> Result<&UpstreamClient, Value> {
+        self.client
+            .as_ref()
+            .map_err(|message| json!({ "error": { "kind": "unavailable", "message": message } }))
+    }
+}
+
+// 每次启动随机生成 32 字节密钥，转成十六进制字符串
+fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).expect("failed to generate proxy secret");
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// 按 URL 查询参数的规则编码，除字母、数字和 -._~ 外都转成 %XX
+fn encode_query_value(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
         })
-        .await;
+        .collect()
+}
+
+// 上游客户端创建失败或 HTTP 代理启动失败时返回错误信息，由前端展示
+#[tauri::command]
+fn proxy_status(state: tauri::State<'_, Backend>) -> Result<(), String> {
+    state.client.as_ref().map_err(Clone::clone)?;
+    match &state.http_error {
+        Some(error) => Err(error.clone()),
+        None => Ok(()),
+    }
+}
+
+// 日志文件路径，前端展示给用户用于反馈问题
+#[tauri::command]
+fn log_file_path(state: tauri::State<'_, Backend>) -> Option<String> {
+    state
+        .log_file
+        .as_ref()
+        .map(|path| path.display().to_string())
+}
+
+// 执行一个具名操作，body 中 POST 操作为上游 JSON，GET 操作为页面文本
+// 错误与 HTTP 代理使用相同的信封格式
+// 带 request_id 的请求可以通过 proxy_cancel 取消，前端丢弃请求时调用
+#[tauri::command]
+async fn proxy_call(
+    state: tauri::State<'_, Backend>,
+    operation: String,
+    token: Option<String>,
+    params: Value,
+    request_id: Option<u64>,
+) -> Result<CallResult, Value> {
+    let client = state.client()?;
+    let op = Operation::from_name(&operation).map_err(|e| e.envelope())?;
+    let params: OperationParams = serde_json::from_value(params)
+        .map_err(|e| ProxyError::InvalidParams(e.to_string()).envelope())?;
+
+    let cancelled = match request_id {
+        Some(id) => Some(state.requests().register(id).ok_or_else(cancelled_error)?),
+        None => None,
+    };
+    let cancelled = async {
+        match cancelled {
+            Some(rx) => _ = rx.await,
+            None => std::future::pending().await,
+        }
+    };
+    let started = Instant::now();
+    // 取消时丢弃 reqwest 的 Future，连接随之关闭
+    let response = tokio::select! {
+        response = client.execute(op, params, token.as_deref()) => response,
+        () = cancelled => {
+            if let Some(id) = request_id {
+                state.requests().finish(id);
+            }
+            return Err(cancelled_error());
+        }
+    };
+    if let Some(id) = request_id {
+        state.requests().finish(id);
+    }
+    let response = response.map_err(|e| e.envelope())?;
+
+    Ok(CallResult {
+        status: response.status,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+        date: response.date,
+        body: match response.body {
+            UpstreamBody::Json(json_value) => json_value,
+            UpstreamBody::Text(text) => Value::String(text),
+        },
+    })
+}
+
+// 取消 proxy_call，请求还没有登记时记下取消，登记时直接返回
+#[tauri::command]
+fn proxy_cancel(state: tauri::State<'_, Backend>, request_id: u64) {
+    state.requests().cancel(request_id);
+}
+
+fn cancelled_error() -> Value {
+    json!({ "error": { "kind": "cancelled", "message": "请求已取消" } })
+}
+
+// 退出登录时清空上游会话 cookie
+#[tauri::command]
+fn proxy_reset_session(state: tauri::State<'_, Backend>) -> Result<(), Value> {
+    state.client()?.reset_session();
+    Ok(())
+}
+
+// 弹出系统的保存对话框，把前端生成的文本写入用户选择的文件
+// 用户取消时返回 false；对话框打开期间只等待回调，不占用 Tokio 工作线程
+#[tauri::command]
+async fn save_text_file(
+    app: tauri::AppHandle,
+    file_name: String,
+    contents: String,
+) -> Result<bool, String> {
+    let extension = file_name.rsplit_once('.').map(|(_, ext)| ext.to_string());
+    let mut dialog = app.dialog().file().set_file_name(&file_name);
+    if let Some(extension) = &extension {
+        dialog = dialog.add_filter(extension.to_uppercase(), &[extension.as_str()]);
+    }
+    let (tx, rx) = oneshot::channel();
+    dialog.save_file(move |path| {
+        let _ = tx.send(path);
+    });
+    let Some(path) = rx.await.ok().flatten() else {
+        return Ok(false);
+    };
+    let path = path
+        .into_path()
+        .map_err(|e| format!("无法保存到该位置: {e}"))?;
+    std::fs::write(&path, contents).map_err(|e| format!("写入 {} 失败: {e}", path.display()))?;
+    Ok(true)
+}
+
+// 读取 funky-proxy.toml 和 FUNKY_PROXY_* 环境变量，创建 IPC 使用的上游客户端
+// 配置中没有指定日志文件时写入应用数据目录下的 logs/funky-lesson.log
+// 设置了 FUNKY_LESSON_HTTP_PROXY=1 时同时启动 HTTP 代理
+fn start_backend(data_dir: Option<PathBuf>) -> (Backend, Option<ProxyHandle>) {
+    let mut config = match ProxyConfig::load(None) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to load proxy config: {e}");
+            let client = Err(format!("代理配置有误: {e}"));
+            return (Backend::new(client, None, None), None);
+        }
+    };
+    if config.log_file.is_none() && !std::env::var(LOG_FILE_ENV).is_ok_and(|v| v == "0") {
+        config.log_file = data_dir.map(|dir| dir.join("logs").join("funky-lesson.log"));
+    }
+    funky_lesson_proxy::init_logger(&config);
+
+    let client = UpstreamClient::new(&config).map_err(|e| {
+        eprintln!("Failed to create upstream client: {e}");
+        format!("上游客户端创建失败: {e}")
     });
+
+    let log_file = config.log_file.clone();
+    let (http, http_error) = if std::env::var(HTTP_PROXY_ENV).is_ok_and(|v| v == "1") {
+        // 没有配置密钥时随机生成一个，浏览器通过下面输出的 ?proxy=&secret= 地址打开前端即可访问
+        let secret = config.shared_secret.clone().unwrap_or_else(generate_secret);
+        match ProxyServer::new(config).shared_secret(&secret).start() {
+            Ok(handle) => {
+                // 随机生成的密钥只能从这里拿到，日志文件中的密钥会被隐藏，所以只输出到控制台
+                let query = format!(
+                    "proxy={}&secret={}",
+                    encode_query_value(&handle.url()),
+                    encode_query_value(&secret)
+                );
+                println!(
+                    "HTTP proxy listening on {}, open the frontend at {FRONTEND_DEV_URL}/?{query}",
+                    handle.url()
+                );
+                (Some(handle), None)
+            }
+            Err(e) => {
+                eprintln!("Failed to start proxy: {e}");
+                (None, Some(format!("内置 HTTP 代理启动失败: {e}")))
+            }
+        }
+    } else {
+        (None, None)
+    };
+
+    (Backend::new(client, log_file, http_error), http)
+}
+
+fn run_app() {
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
-        .invoke_handler(tauri::generate_handler![])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .plugin(tauri_plugin_dialog::init())
+        .setup(|app| {
+            let data_dir = app
+                .path()
+                .app_data_dir()
+                .inspect_err(|e| eprintln!("Failed to resolve app data dir: {e}"))
+                .ok();
+            let (backend, http) = start_backend(data_dir);
+            app.manage(backend);
+            app.manage(HttpProxy(http));
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            proxy_status,
+            proxy_call,
+            proxy_cancel,
+            proxy_reset_session,
+            log_file_path,
+            save_text_file
+        ])
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            // 退出前关闭 HTTP 代理，等待进行中的请求完成
+            if let tauri::RunEvent::Exit = event
+                && let Some(http) = app.try_state::<HttpProxy>()
+                && let Some(http) = &http.0
+            {
+                http.shutdown();
+            }
+        });
+}
+
+#[cfg(not(mobile))]
+#[tokio::main]
+pub async fn run() {
+    run_app();
 }
 
 #[cfg(mobile)]
@@ -24,15 +351,5 @@ pub async fn run() {
 pub async fn run() {
     // Mobile implementation
 
-    tokio::spawn(async move {
-        let _ = tokio::task::spawn_blocking(|| {
-            let _ = funky_lesson_proxy::main();
-        })
-        .await;
-    });
-    tauri::Builder::default()
-        .plugin(tauri_plugin_shell::init())
-        .invoke_handler(tauri::generate_handler![])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+    run_app();
 }
@@ -1,13 +1,25 @@
+use crate::catalog::CatalogBrowser;
+use crate::conflict::{self, Issue};
+use crate::enroll::ProxyTransport;
 use crate::external_link::ExternalLink;
+use crate::plan::{CoursePlan, Plan};
+use crate::proxy::{self, ProxyError};
+use crate::schedule::ClassDetails;
+use crate::timetable::Timetable;
 use funky_lesson_core::{
-    client::gloo,
     crypto,
     error::{ErrorKind, Result},
-    model::structs::{BatchInfo, CourseInfo, EnrollmentStatus},
+    model::structs::{BatchInfo, CourseInfo},
 };
+use funky_lesson_engine::{
+    Course, CoursePolicy, CourseStatus, Engine, Options, PauseReason, Phase, Snapshot, StopReason,
+    WasmTimer, parse_upstream_time,
+};
+use futures::StreamExt;
 use leptos::prelude::*;
 use leptos::task::spawn_local;
 use leptos::*;
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{LazyLock, Mutex};
 
@@ -76,46 +88,84 @@ pub fn toast_warning(message: impl Into<String>) {
 }
 
 // Leptos资源和信号
-#[derive(Clone)]
+#[derive(Clone, Copy)]
 pub struct AppState {
+    // 登录使用的学号，用来区分保存的选课计划
+    pub username: RwSignal<Option<String>>,
     pub token: RwSignal<Option<String>>,
     pub batch_id: RwSignal<Option<String>>,
     pub batch_list: RwSignal<Vec<BatchInfo>>,
+    // 批次代码对应的开始选课时间，上游没有提供时缺省
+    pub batch_begin_times: RwSignal<HashMap<String, u64>>,
     pub selected_courses: RwSignal<Vec<CourseInfo>>,
     pub favorite_courses: RwSignal<Vec<CourseInfo>>,
-    pub enrollment_status: RwSignal<EnrollmentStatus>,
-    pub should_continue: RwSignal<bool>,
+    // 已选和待选教学班的课程号和上课时间，按教学班 ID 索引
+    pub class_details: RwSignal<HashMap<String, ClassDetails>>,
+    // 当前批次的选课计划，修改后通过 update_plan 保存
+    pub plan: RwSignal<Plan>,
+    // 选课引擎的最新状态
+    pub enrollment_status: RwSignal<Snapshot>,
+    // 当前的选课引擎，只在浏览器线程中使用
+    pub engine: StoredValue<Option<Engine>, LocalStorage>,
 }
 
 impl AppState {
     pub fn new() -> Self {
         Self {
+            username: RwSignal::new(None),
             token: RwSignal::new(None),
             batch_id: RwSignal::new(None),
             batch_list: RwSignal::new(Vec::new()),
+            batch_begin_times: RwSignal::new(HashMap::new()),
             selected_courses: RwSignal::new(Vec::new()),
             favorite_courses: RwSignal::new(Vec::new()),
-            enrollment_status: RwSignal::new(EnrollmentStatus::default()),
-            should_continue: RwSignal::new(false),
+            class_details: RwSignal::new(HashMap::new()),
+            plan: RwSignal::new(Plan::default()),
+            enrollment_status: RwSignal::new(Snapshot::default()),
+            engine: StoredValue::new_local(None),
         }
     }
 
     pub fn reset_for_login(&self) {
+        self.username.set(None);
         self.token.set(None);
         self.batch_id.set(None);
         self.batch_list.set(Vec::new());
+        self.batch_begin_times.set(HashMap::new());
         self.selected_courses.set(Vec::new());
         self.favorite_courses.set(Vec::new());
-        self.enrollment_status.set(EnrollmentStatus::default());
-        self.should_continue.set(false);
+        self.class_details.set(HashMap::new());
+        self.plan.set(Plan::default());
+        self.enrollment_status.set(Snapshot::default());
+        self.stop_engine();
+    }
+
+    // 修改选课计划并保存到本地
+    pub fn update_plan(&self, f: impl FnOnce(&mut Plan)) {
+        self.plan.update(f);
+        if let (Some(username), Some(batch_id)) =
+            (self.username.get_untracked(), self.batch_id.get_untracked())
+        {
+            self.plan
+                .with_untracked(|plan| plan.save(&username, &batch_id));
+        }
+    }
+
+    fn stop_engine(&self) {
+        if let Some(engine) = self.engine.get_value() {
+            engine.stop();
+        }
+        self.engine.set_value(None);
     }
 
     pub fn reset_for_batch_selection(&self) {
         self.batch_id.set(None);
         self.selected_courses.set(Vec::new());
         self.favorite_courses.set(Vec::new());
-        self.enrollment_status.set(EnrollmentStatus::default());
-        self.should_continue.set(false);
+        self.class_details.set(HashMap::new());
+        self.plan.set(Plan::default());
+        self.enrollment_status.set(Snapshot::default());
+        self.stop_engine();
     }
 }
 
@@ -202,6 +252,11 @@ pub fn ToastContainer() -> impl IntoView {
     }
 }
 
+// 代理错误转换为核心库错误
+fn proxy_error(e: ProxyError) -> ErrorKind {
+    ErrorKind::ParseError(e.to_string())
+}
+
 // 登录函数
 pub async fn login(
     username: &str,
@@ -210,16 +265,14 @@ pub async fn login(
     uuid: &str,
     app_state: &AppState,
 ) -> Result<()> {
-    // 初始化
-    gloo::create_client().await?;
-
     // 获取AES密钥
-    let aes_key = gloo::get_aes_key_proxy().await?;
+    let aes_key = proxy::get_aes_key().await.map_err(proxy_error)?;
 
     // 加密密码并登录
     let encrypted_password = crypto::encrypt_password(password, &aes_key)?;
-    let login_resp =
-        gloo::send_login_request_proxy(username, &encrypted_password, captcha, uuid).await?;
+    let login_resp = proxy::login(username, &encrypted_password, captcha, uuid)
+        .await
+        .map_err(proxy_error)?;
 
     if login_resp["code"] == 200 && login_resp["msg"] == "登录成功" {
         let token = login_resp["data"]["token"]
@@ -227,12 +280,24 @@ pub async fn login(
             .ok_or_else(|| ErrorKind::ParseError("Invalid token".to_string()))?
             .to_string();
 
-        let batch_list: Vec<BatchInfo> =
-            serde_json::from_value(login_resp["data"]["student"]["electiveBatchList"].clone())?;
+        let batch_json = &login_resp["data"]["student"]["electiveBatchList"];
+        let batch_list: Vec<BatchInfo> = serde_json::from_value(batch_json.clone())?;
+        let begin_times = batch_json
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|batch| {
+                let code = batch["code"].as_str()?;
+                let begin = parse_upstream_time(batch["beginTime"].as_str()?)?;
+                Some((code.to_string(), begin))
+            })
+            .collect();
 
         // 更新状态
+        app_state.username.set(Some(username.to_string()));
         app_state.token.set(Some(token));
         app_state.batch_list.set(batch_list);
+        app_state.batch_begin_times.set(begin_times);
         Ok(())
     } else {
         Err(ErrorKind::ParseError(login_resp["msg"].to_string()).into())
@@ -241,7 +306,7 @@ pub async fn login(
 
 // 获取验证码
 pub async fn get_captcha() -> Result<(String, String)> {
-    gloo::get_captcha_proxy().await
+    Ok(proxy::get_captcha().await.map_err(proxy_error)?)
 }
 
 // 设置选课批次
@@ -257,7 +322,9 @@ pub async fn set_batch(batch_idx: usize, app_state: &AppState) -> Result<()> {
     }
 
     let batch_id = batch_list[batch_idx].code.clone();
-    let resp = gloo::set_batch_proxy(&batch_id, &token).await?;
+    let resp = proxy::set_batch(&token, &batch_id)
+        .await
+        .map_err(proxy_error)?;
 
     if resp["code"] != 200 {
         return Err(ErrorKind::ParseError("Failed to set batch".to_string()).into());
@@ -278,144 +345,567 @@ pub async fn get_courses(app_state: &AppState) -> Result<()> {
         .get()
         .ok_or_else(|| ErrorKind::ParseError("No batch id selected".to_string()))?;
 
-    let selected = gloo::get_selected_courses_proxy(&token, &batch_id).await?;
+    let selected = proxy::get_selected_courses(&token, &batch_id)
+        .await
+        .map_err(proxy_error)?;
     let selected_courses: Vec<CourseInfo> = if selected["code"] == 200 {
         serde_json::from_value(selected["data"].clone())?
     } else {
         return Err(ErrorKind::CourseError(selected["msg"].to_string()).into());
     };
 
-    let favorite = gloo::get_favorite_courses_proxy(&token, &batch_id).await?;
+    let favorite = proxy::get_favorite_courses(&token, &batch_id)
+        .await
+        .map_err(proxy_error)?;
     let favorite_courses: Vec<CourseInfo> = if favorite["code"] == 200 {
         serde_json::from_value(favorite["data"].clone())?
     } else {
         return Err(ErrorKind::CourseError(favorite["msg"].to_string()).into());
     };
 
+    if let Some(username) = app_state.username.get_untracked() {
+        app_state.plan.set(Plan::load(&username, &batch_id));
+    }
+    // CourseInfo 中没有课程号和上课时间，从原始数据中取出
+    let added = app_state.plan.get_untracked().added;
+    let raw_courses = [&selected["data"], &favorite["data"]]
+        .into_iter()
+        .flat_map(|data| data.as_array().into_iter().flatten())
+        .chain(&added);
+    let class_details = raw_courses
+        .filter_map(|course| {
+            let id = course["JXBID"].as_str()?;
+            Some((id.to_string(), ClassDetails::from_value(course)))
+        })
+        .collect();
+
+    // 待选课程为网站上的收藏加上在课程目录中加入的教学班
+    let mut favorite_courses = favorite_courses;
+    for course in added {
+        match serde_json::from_value::<CourseInfo>(course) {
+            Ok(course) if !favorite_courses.iter().any(|c| c.JXBID == course.JXBID) => {
+                favorite_courses.push(course)
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("Ignoring invalid saved class: {e}"),
+        }
+    }
+
     app_state.selected_courses.set(selected_courses);
     app_state.favorite_courses.set(favorite_courses);
+    app_state.class_details.set(class_details);
     Ok(())
 }
 
-// 选课函数
+// 选课函数，所有 worker 退出后返回结束原因
+// 计划中排除的课程不参与，其余课程按各自的策略请求
+// start_at 为开始的服务器时间，之前只定期检查登录状态
+// watch_ms 不为 None 时进入盯课模式，按这个间隔查询余量，有空位时才发送选课请求
+// 已有引擎在运行时不会重复开始，返回 None
 pub async fn enroll_courses(
     courses: Vec<CourseInfo>,
-    try_if_capacity_full: bool,
+    max_rps: Option<u32>,
+    start_at: Option<u64>,
+    watch_ms: Option<u64>,
     app_state: &AppState,
-) -> Result<()> {
+) -> Result<Option<StopReason>> {
+    if app_state
+        .engine
+        .with_value(|engine| engine.as_ref().is_some_and(Engine::is_active))
+    {
+        return Ok(None);
+    }
+    let plan = app_state.plan.get_untracked();
+    let courses: Vec<CourseInfo> = courses
+        .into_iter()
+        .filter(|c| plan.course(&c.JXBID).include)
+        .collect();
     if courses.is_empty() {
-        return Ok(());
+        return Ok(Some(StopReason::Completed));
     }
 
-    let token = app_state
-        .token
-        .get()
-        .ok_or_else(|| ErrorKind::ParseError("No token available".to_string()))?;
+    if app_state.token.get().is_none() {
+        return Err(ErrorKind::ParseError("No token available".to_string()).into());
+    }
     let batch_id = app_state
         .batch_id
         .get()
         .ok_or_else(|| ErrorKind::ParseError("No batch id selected".to_string()))?;
 
-    app_state.should_continue.set(true);
-    app_state.enrollment_status.update(|status| {
-        status.is_running = true;
-        status.course_statuses = courses
-            .iter()
-            .map(|c| format!("[{}]等待中", c.KCM))
-            .collect();
+    // 同组的课程按偏好顺序排在一起
+    let courses = plan
+        .arrange(courses, |c| &c.JXBID)
+        .into_iter()
+        .map(|(c, group)| {
+            let settings = plan.course(&c.JXBID);
+            Course {
+                id: c.JXBID,
+                name: c.KCM,
+                class_type: c.teaching_class_type.unwrap_or_default(),
+                secret_val: c.secret_val.unwrap_or_default(),
+                group,
+                policy: CoursePolicy {
+                    retry_when_full: settings.retry_when_full,
+                    max_attempts: settings.max_attempts,
+                    give_up_at_ms: settings.give_up_at,
+                },
+            }
+        })
+        .collect();
+    let engine = Engine::new(
+        courses,
+        Options {
+            max_rps,
+            watch_ms,
+            ..Options::default()
+        },
+    );
+    app_state.engine.set_value(Some(engine.clone()));
+    app_state.enrollment_status.set(engine.snapshot());
+
+    // 引擎状态变化时刷新界面，直到结束且所有 worker 退出
+    let mut events = engine.subscribe();
+    let watched = engine.clone();
+    let enrollment = app_state.enrollment_status;
+    spawn_local(async move {
+        while events.next().await.is_some() {
+            let snapshot = watched.snapshot();
+            let exited = matches!(snapshot.phase, Phase::Finished(_)) && snapshot.workers == 0;
+            enrollment.set(snapshot);
+            if exited {
+                break;
+            }
+        }
     });
 
-    let courses_count = courses.len();
+    let transport = ProxyTransport {
+        token: app_state.token,
+        batch_id,
+    };
+    Ok(Some(match start_at {
+        Some(at_ms) => engine.start_at(transport, WasmTimer, at_ms).await,
+        None => engine.start(transport, WasmTimer).await,
+    }))
+}
 
-    // 创建工作任务
-    for thread_id in 0..12 {
-        let token = token.clone();
-        let batch_id = batch_id.clone();
-        let courses = courses.clone();
-        let app_state = app_state.clone();
+// 当前批次的开始选课时间
+fn batch_begin_time(app_state: &AppState) -> Option<u64> {
+    let batch_id = app_state.batch_id.get()?;
+    app_state
+        .batch_begin_times
+        .with(|times| times.get(&batch_id).copied())
+}
 
-        spawn_local(async move {
-            let mut course_idx = thread_id % courses_count;
+// Unix 毫秒时间戳转为 datetime-local 输入框使用的本地时间
+fn format_datetime_local(ms: u64) -> String {
+    let date = js_sys::Date::new(&wasm_bindgen::JsValue::from_f64(ms as f64));
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        date.get_full_year(),
+        date.get_month() + 1,
+        date.get_date(),
+        date.get_hours(),
+        date.get_minutes(),
+        date.get_seconds()
+    )
+}
 
-            while app_state.should_continue.get() {
-                let course = &courses[course_idx];
+// 解析 datetime-local 输入框的值，不带时区时按本地时间解析
+fn parse_datetime_local(value: &str) -> Option<u64> {
+    let ms = js_sys::Date::parse(value);
+    (ms.is_finite() && ms >= 0.0).then_some(ms as u64)
+}
 
-                // 更新状态
-                app_state.enrollment_status.update(|status| {
-                    status.total_requests += 1;
-                });
+// 倒计时显示为 HH:MM:SS，不足一秒按一秒显示
+fn format_countdown(ms: u64) -> String {
+    let seconds = ms.div_ceil(1000);
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
 
-                // 尝试选课
-                let result = gloo::select_course_proxy(
-                    &token,
-                    &batch_id,
-                    &course.teaching_class_type.clone().unwrap_or_default(),
-                    &course.JXBID,
-                    &course.secret_val.clone().unwrap_or_default(),
-                )
-                .await;
+// Unix 毫秒时间戳转为本地时间 HH:MM:SS.mmm
+fn format_time(ms: u64) -> String {
+    let date = js_sys::Date::new(&wasm_bindgen::JsValue::from_f64(ms as f64));
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        date.get_hours(),
+        date.get_minutes(),
+        date.get_seconds(),
+        date.get_milliseconds()
+    )
+}
 
-                match result {
-                    Ok(json) => {
-                        let code = json["code"].as_i64().unwrap_or(0);
-                        let msg = json["msg"].as_str().unwrap_or("");
+// 实时状态中的一行，只在这门课程的状态变化时重新渲染
+#[component]
+fn CourseStateRow(index: usize, enrollment: RwSignal<Snapshot>) -> impl IntoView {
+    let state = Memo::new(move |_| enrollment.get().courses.get(index).cloned());
+
+    move || {
+        state.get().map(|state| {
+            let color = match state.status {
+                CourseStatus::Selected | CourseStatus::AlreadySelected => "text-green-300",
+                CourseStatus::Skipped | CourseStatus::GaveUp => "text-white/60",
+                CourseStatus::Full | CourseStatus::NotStarted | CourseStatus::Waiting => {
+                    "text-yellow-200"
+                }
+                _ => "text-red-300",
+            };
+            let mut details = vec![format!("第{}次", state.attempts)];
+            if state.checks > 0 {
+                let remaining = state.remaining.map_or("-".to_string(), |n| n.to_string());
+                details.push(format!("已查询余量{}次 · 余量 {remaining}", state.checks));
+            }
+            if let Some(ms) = state.latency_ms {
+                details.push(format!("{ms}ms"));
+            }
+            if let (Some(code), Some(msg)) = (state.last_code, &state.last_msg) {
+                details.push(format!("{code} {msg}"));
+            }
+            if let Some(e) = &state.last_error {
+                details.push(format!("最近错误: {e}"));
+            }
+            if let Some(ms) = state.selected_at {
+                details.push(format!("成功于 {}", format_time(ms)));
+            }
 
-                        let status = match (code, msg) {
-                            (200, _) => {
-                                app_state.should_continue.set(false);
-                                "选课成功"
-                            }
-                            (500, "该课程已在选课结果中") => {
-                                app_state.should_continue.set(false);
-                                "已选"
-                            }
-                            (500, "本轮次选课暂未开始") => "未开始",
-                            (500, "课容量已满") if !try_if_capacity_full => {
-                                app_state.should_continue.set(false);
-                                "已满"
-                            }
-                            (500, "课容量已满") => "等待中",
-                            (500, "参数校验不通过") => "参数错误",
-                            (401, _) => {
-                                app_state.should_continue.set(false);
-                                "未登录"
-                            }
-                            _ => "失败",
-                        };
+            view! {
+                <div class="text-sm leading-relaxed">
+                    <span class="text-white">{format!("[{}]", state.course.name)}</span>
+                    <span class=format!("ml-1 font-bold {color}")>{state.status.to_string()}</span>
+                    <span class="ml-2 text-xs text-white/50">{details.join(" · ")}</span>
+                </div>
+            }
+        })
+    }
+}
 
-                        app_state.enrollment_status.update(|s| {
-                            s.course_statuses[course_idx] = format!("[{}]{}", course.KCM, status);
-                        });
-                    }
-                    Err(e) => {
-                        app_state.enrollment_status.update(|s| {
-                            s.course_statuses[course_idx] = format!("[{}]请求错误", course.KCM);
-                        });
-                        log::error!("请求错误: {e:?}");
+// 登录失效后重新登录，恢复原来的批次并继续选课
+pub async fn relogin(
+    username: &str,
+    password: &str,
+    captcha: &str,
+    uuid: &str,
+    app_state: &AppState,
+) -> Result<()> {
+    let batch_id = app_state
+        .batch_id
+        .get_untracked()
+        .ok_or_else(|| ErrorKind::ParseError("No batch id selected".to_string()))?;
+
+    login(username, password, captcha, uuid, app_state).await?;
+
+    let batch_idx = app_state
+        .batch_list
+        .get_untracked()
+        .iter()
+        .position(|batch| batch.code == batch_id)
+        .ok_or_else(|| ErrorKind::ParseError("原选课批次已不可用".to_string()))?;
+    set_batch(batch_idx, app_state).await?;
+
+    // 引擎保留了课程队列和计数，恢复后从暂停的位置继续
+    if let Some(engine) = app_state.engine.get_value() {
+        engine.resume();
+    }
+    Ok(())
+}
+
+// 待选课程的"多选一"分组设置
+#[component]
+fn GroupPicker(id: String, app_state: AppState, disabled: ReadSignal<bool>) -> impl IntoView {
+    let id = StoredValue::new(id);
+    let plan = app_state.plan;
+    let position = Memo::new(move |_| plan.with(|plan| id.with_value(|id| plan.position(id))));
+
+    let on_change = move |ev: web_sys::Event| {
+        let group = match event_target_value(&ev).as_str() {
+            "" => None,
+            // 新建一组
+            "new" => Some(usize::MAX),
+            group => group.parse().ok(),
+        };
+        id.with_value(|id| app_state.update_plan(|plan| plan.assign(id, group)));
+    };
+    let raise = move |_: web_sys::MouseEvent| {
+        id.with_value(|id| app_state.update_plan(|plan| plan.raise(id)))
+    };
+
+    view! {
+        <div class="flex items-center gap-1 text-xs text-white/80">
+            {move || {
+                position
+                    .get()
+                    .map(|(_, rank)| {
+                        view! {
+                            <span class="text-yellow-200">{format!("第{}志愿", rank + 1)}</span>
+                            <Show when=move || { rank > 0 }>
+                                <button
+                                    type="button"
+                                    class="px-1 rounded bg-white/10 hover:bg-white/20 disabled:opacity-50"
+                                    title="提高偏好"
+                                    on:click=raise
+                                    disabled=move || disabled.get()
+                                >
+                                    "↑"
+                                </button>
+                            </Show>
+                        }
+                    })
+            }}
+            <select
+                class="bg-white/10 border border-white/20 rounded px-1 text-white text-xs"
+                on:change=on_change
+                disabled=move || disabled.get()
+            >
+                {move || {
+                    let current = position.get().map(|(group, _)| group);
+                    let groups = plan.with(|plan| plan.groups.len());
+                    let options = (0..groups)
+                        .map(|group| {
+                            view! {
+                                <option value=group.to_string() selected={current == Some(group)}>
+                                    {format!("多选一 组{}", group + 1)}
+                                </option>
+                            }
+                        })
+                        .collect_view();
+                    view! {
+                        <option value="" selected={current.is_none()}>"单独选"</option>
+                        {options}
+                        <option value="new">"新建一组"</option>
                     }
-                }
+                }}
+            </select>
+        </div>
+    }
+}
 
-                if !app_state.should_continue.get() {
-                    break;
-                }
+// 把在课程目录中加入的教学班移出待选
+fn remove_added(id: &str, app_state: &AppState) {
+    app_state.update_plan(|plan| plan.remove(id));
+    app_state
+        .favorite_courses
+        .update(|courses| courses.retain(|c| c.JXBID != id));
+}
 
-                course_idx = (course_idx + 1) % courses_count;
+// 在选课网站上收藏或取消收藏，成功后重新获取已选和待选课程
+pub async fn set_favorite(course: &CourseInfo, favorite: bool, app_state: &AppState) -> Result<()> {
+    let token = app_state
+        .token
+        .get_untracked()
+        .ok_or_else(|| ErrorKind::ParseError("No token available".to_string()))?;
+    let batch_id = app_state
+        .batch_id
+        .get_untracked()
+        .ok_or_else(|| ErrorKind::ParseError("No batch id selected".to_string()))?;
+    let class_type = course.teaching_class_type.clone().unwrap_or_default();
+
+    let resp = if favorite {
+        proxy::add_favorite(
+            &token,
+            &batch_id,
+            &class_type,
+            &course.JXBID,
+            course.secret_val.as_deref().unwrap_or_default(),
+        )
+        .await
+    } else {
+        proxy::remove_favorite(&token, &batch_id, &class_type, &course.JXBID).await
+    }
+    .map_err(proxy_error)?;
+    if resp["code"] != 200 {
+        let msg = resp["msg"].as_str().unwrap_or("未知错误");
+        return Err(ErrorKind::CourseError(msg.to_string()).into());
+    }
 
-                // 短暂延迟避免请求过快
-                set_timeout(200).await;
+    // 已经收藏到网站上的课程不再需要本地保存的数据
+    if favorite {
+        app_state.update_plan(|plan| plan.forget_added(&course.JXBID));
+    }
+    get_courses(app_state).await
+}
+
+// 待选课程的来源和收藏操作：网站收藏可以取消收藏，课程目录中加入的可以收藏到网站或移出
+#[component]
+fn FavoriteActions(
+    course: CourseInfo,
+    app_state: AppState,
+    disabled: ReadSignal<bool>,
+) -> impl IntoView {
+    let course = StoredValue::new(course);
+    let added = Memo::new(move |_| {
+        app_state
+            .plan
+            .with(|plan| course.with_value(|course| plan.is_added(&course.JXBID)))
+    });
+    let (busy, set_busy) = signal(false);
+
+    let change = move |favorite: bool| {
+        set_busy.set(true);
+        spawn_local(async move {
+            let course = course.get_value();
+            match set_favorite(&course, favorite, &app_state).await {
+                Ok(()) if favorite => toast_success(format!("已收藏：{}", course.KCM)),
+                Ok(()) => toast_success(format!("已取消收藏：{}", course.KCM)),
+                Err(e) => {
+                    let action = if favorite { "收藏" } else { "取消收藏" };
+                    toast_error(format!("{action}失败：{e:?}"));
+                }
             }
+            set_busy.set(false);
         });
+    };
+    let button_class = "ml-2 px-1 rounded bg-white/10 hover:bg-white/20 disabled:opacity-50";
+
+    view! {
+        <Show
+            when=move || added.get()
+            fallback=move || {
+                view! {
+                    <button
+                        type="button"
+                        class=button_class
+                        on:click=move |_| change(false)
+                        disabled=move || busy.get() || disabled.get()
+                    >
+                        "取消收藏"
+                    </button>
+                }
+            }
+        >
+            <span class="ml-2 text-purple-200">"来自课程目录"</span>
+            <button
+                type="button"
+                class=button_class
+                on:click=move |_| change(true)
+                disabled=move || busy.get() || disabled.get()
+            >
+                "收藏到网站"
+            </button>
+            <button
+                type="button"
+                class=button_class
+                on:click=move |_| course.with_value(|course| remove_added(&course.JXBID, &app_state))
+                disabled=move || busy.get() || disabled.get()
+            >
+                "移出"
+            </button>
+        </Show>
     }
+}
 
-    Ok(())
+// 待选课程中勾选参与抢课的数量
+fn included_count(app_state: &AppState) -> usize {
+    let plan = app_state.plan.get();
+    app_state.favorite_courses.with(|courses| {
+        courses
+            .iter()
+            .filter(|c| plan.course(&c.JXBID).include)
+            .count()
+    })
+}
+
+// 待选课程是否参与抢课，以及满员重试、请求次数上限和放弃时间
+// 盯课模式下引擎总是满员重试，此时满员重试显示为勾选且不能修改
+#[component]
+fn PolicyEditor(
+    id: String,
+    app_state: AppState,
+    disabled: ReadSignal<bool>,
+    watch_mode: ReadSignal<bool>,
+) -> impl IntoView {
+    let id = StoredValue::new(id);
+    let plan = app_state.plan;
+    let settings = Memo::new(move |_| plan.with(|plan| id.with_value(|id| plan.course(id))));
+    let update = move |f: &dyn Fn(&mut CoursePlan)| {
+        id.with_value(|id| app_state.update_plan(|plan| plan.update_course(id, f)));
+    };
+
+    let on_include = move |ev: web_sys::Event| {
+        let checked = event_target_checked(&ev);
+        update(&|course| course.include = checked);
+    };
+    let on_retry = move |ev: web_sys::Event| {
+        let checked = event_target_checked(&ev);
+        update(&|course| course.retry_when_full = checked);
+    };
+    // 留空或填 0 为不限
+    let on_max_attempts = move |ev: web_sys::Event| {
+        let max = event_target_value(&ev)
+            .trim()
+            .parse()
+            .ok()
+            .filter(|&max| max > 0);
+        update(&|course| course.max_attempts = max);
+    };
+    let on_give_up_at = move |ev: web_sys::Event| {
+        let at = parse_datetime_local(&event_target_value(&ev));
+        update(&|course| course.give_up_at = at);
+    };
+
+    view! {
+        <div class="flex flex-wrap items-center gap-3 mt-2 text-xs text-white/80">
+            <label class="flex items-center gap-1">
+                <input
+                    type="checkbox"
+                    prop:checked=move || settings.get().include
+                    on:change=on_include
+                    disabled=move || disabled.get()
+                />
+                "参与抢课"
+            </label>
+            <label
+                class="flex items-center gap-1"
+                title=move || watch_mode.get().then_some("盯课模式下抢输后总是继续盯，这个设置不生效")
+            >
+                <input
+                    type="checkbox"
+                    prop:checked=move || watch_mode.get() || settings.get().retry_when_full
+                    on:change=on_retry
+                    disabled=move || disabled.get() || watch_mode.get()
+                />
+                {move || if watch_mode.get() { "满员重试（盯课时总是重试）" } else { "满员重试" }}
+            </label>
+            <label class="flex items-center gap-1">
+                "最多"
+                <input
+                    type="number"
+                    min="0"
+                    placeholder="不限"
+                    class="w-16 bg-white/10 border border-white/20 rounded px-1 text-white"
+                    prop:value=move || {
+                        settings.get().max_attempts.map(|max| max.to_string()).unwrap_or_default()
+                    }
+                    on:change=on_max_attempts
+                    disabled=move || disabled.get()
+                />
+                "次"
+            </label>
+            <label class="flex items-center gap-1">
+                "截止"
+                <input
+                    type="datetime-local"
+                    step="1"
+                    class="bg-white/10 border border-white/20 rounded px-1 text-white"
+                    prop:value=move || {
+                        settings.get().give_up_at.map(format_datetime_local).unwrap_or_default()
+                    }
+                    on:change=on_give_up_at
+                    disabled=move || disabled.get()
+                />
+            </label>
+        </div>
+    }
 }
 
 // 停止选课
 pub fn stop_enrollment(app_state: &AppState) {
-    app_state.should_continue.set(false);
-    app_state.enrollment_status.update(|status| {
-        status.is_running = false;
-    });
+    if let Some(engine) = app_state.engine.get_value() {
+        engine.stop();
+    }
 }
 
 // Utility functions
@@ -444,6 +934,19 @@ pub fn App() -> impl IntoView {
     let (status_message, set_status_message) = signal("请登录".to_string());
     let (step, set_step) = signal(1);
     let (is_enrolling, set_is_enrolling) = signal(false);
+    // 所有请求合计的每秒上限，0 表示不限制
+    let (max_rps, set_max_rps) = signal(Options::default().max_rps.unwrap_or(0));
+    // 定时开始的时间，None 时点击后立即开始
+    let (start_at, set_start_at) = signal(None::<u64>);
+    // 盯课模式和查询余量的间隔（秒）
+    let (watch_mode, set_watch_mode) = signal(false);
+    let (watch_secs, set_watch_secs) = signal(10_u64);
+    // 倒计时使用的本地时间
+    let (now_ms, set_now_ms) = signal(js_sys::Date::now() as u64);
+    set_interval(
+        move || set_now_ms.set(js_sys::Date::now() as u64),
+        std::time::Duration::from_millis(250),
+    );
 
     // Back button handler
     let handle_back = move |_| {
@@ -453,6 +956,11 @@ pub fn App() -> impl IntoView {
                 // 从批次选择回到登录
                 set_step.set(1);
                 app_state.get().reset_for_login();
+                spawn_local(async move {
+                    if let Err(e) = proxy::reset_session().await {
+                        log::warn!("清空会话失败: {e}");
+                    }
+                });
                 set_status_message.set("请重新登录".to_string());
                 toast_info("已返回登录页面");
             }
@@ -585,33 +1093,183 @@ pub fn App() -> impl IntoView {
     };
 
     // 开始抢课
-    let handle_enroll = move |_| {
+    let start_enrollment = move || {
+        let current_state = app_state.get_untracked();
         set_is_enrolling.set(true);
-        let current_state = app_state.get();
-        toast_info("开始抢课...");
+        // 按服务器时间已经过了设定的时间时立即开始，与引擎和倒计时使用同一个时钟
+        let server_now = current_state
+            .enrollment_status
+            .get_untracked()
+            .clock
+            .server_time(js_sys::Date::now() as u64);
+        let at_ms = start_at.get_untracked().filter(|&ms| ms > server_now);
+        match at_ms {
+            Some(ms) => toast_info(format!(
+                "已定时，将在 {} 开始抢课",
+                format_datetime_local(ms).replace('T', " ")
+            )),
+            None if watch_mode.get_untracked() => toast_info("开始盯课，有空位时自动选课"),
+            None => toast_info("开始抢课..."),
+        }
+        let watch_ms = watch_mode
+            .get_untracked()
+            .then(|| watch_secs.get_untracked().max(1) * 1000);
 
         spawn_local(async move {
             let courses = current_state.favorite_courses.get();
-            if let Err(e) = enroll_courses(courses, true, &current_state).await {
-                let error_msg = format!("抢课出错：{e:?}");
-                set_status_message.set(error_msg.clone());
-                toast_error(error_msg);
-                set_is_enrolling.set(false);
+            match enroll_courses(
+                courses,
+                Some(max_rps.get_untracked()).filter(|&rps| rps > 0),
+                at_ms,
+                watch_ms,
+                &current_state,
+            )
+            .await
+            {
+                Ok(Some(StopReason::Completed)) => toast_success("抢课结束"),
+                Ok(Some(StopReason::Stopped)) => toast_warning("已停止抢课，所有请求均已结束"),
+                // 上一次抢课还没有结束，交给它更新按钮状态
+                Ok(None) => {
+                    toast_info("抢课已在进行中");
+                    return;
+                }
+                Err(e) => {
+                    let error_msg = format!("抢课出错：{e:?}");
+                    set_status_message.set(error_msg.clone());
+                    toast_error(error_msg);
+                }
             }
+            set_is_enrolling.set(false);
         });
     };
 
-    // 停止抢课
+    // 参与抢课的课程中重复和时间冲突的问题
+    let issues = Memo::new(move |_| {
+        let state = app_state.get();
+        let plan = state.plan.get();
+        state.favorite_courses.with(|targets| {
+            state.selected_courses.with(|selected| {
+                state
+                    .class_details
+                    .with(|details| conflict::check(targets, selected, details, &plan))
+            })
+        })
+    });
+    // 有问题时先弹窗，逐项移出或确认后再开始
+    let (confirming, set_confirming) = signal(false);
+
+    let handle_enroll = move |_| {
+        // 按钮状态还没更新时的重复点击
+        if is_enrolling.get_untracked() {
+            return;
+        }
+        if included_count(&app_state.get_untracked()) == 0 {
+            toast_warning("没有勾选参与抢课的课程");
+            return;
+        }
+        if issues.with_untracked(|issues| !issues.is_empty()) {
+            set_confirming.set(true);
+            return;
+        }
+        start_enrollment();
+    };
+
+    let handle_confirm_enroll = move |_| {
+        set_confirming.set(false);
+        if included_count(&app_state.get_untracked()) == 0 {
+            toast_warning("没有勾选参与抢课的课程");
+            return;
+        }
+        start_enrollment();
+    };
+
+    // 停止抢课，所有 worker 退出后 handle_enroll 会给出提示
     let handle_stop_enroll = move |_| {
-        set_is_enrolling.set(false);
         let current_state = app_state.get();
         stop_enrollment(&current_state);
-        toast_warning("已停止抢课");
+        toast_info("正在停止抢课...");
     };
 
-    // 初始化时获取验证码 - 现在不会报错了！
+    // 已经停止但还有进行中的请求没有取消完
+    let stopping = Memo::new(move |_| {
+        let snapshot = app_state.get().enrollment_status.get();
+        matches!(snapshot.phase, Phase::Finished(_)) && snapshot.workers > 0
+    });
+
+    // 登录失效时引擎暂停，弹出重新登录窗口
+    let session_expired = Memo::new(move |_| {
+        app_state.get().enrollment_status.get().phase == Phase::Paused(PauseReason::SessionExpired)
+    });
+    let (relogging_in, set_relogging_in) = signal(false);
+
+    // 弹窗打开时换一张新的验证码
     Effect::new(move |_| {
-        handle_get_captcha(());
+        if session_expired.get() {
+            set_captcha.set(String::new());
+            handle_get_captcha(());
+        }
+    });
+
+    // 重新登录后恢复批次并继续抢课
+    let handle_relogin = move |ev: web_sys::SubmitEvent| {
+        ev.prevent_default();
+
+        if password.get().is_empty() {
+            toast_warning("请输入密码");
+            return;
+        }
+        if captcha.get().is_empty() {
+            toast_warning("请输入验证码");
+            return;
+        }
+
+        set_relogging_in.set(true);
+        let current_state = app_state.get();
+        spawn_local(async move {
+            match relogin(
+                &username.get_untracked(),
+                &password.get_untracked(),
+                &captcha.get_untracked(),
+                &captcha_uuid.get_untracked(),
+                &current_state,
+            )
+            .await
+            {
+                Ok(()) => toast_success("重新登录成功，继续抢课"),
+                Err(e) => {
+                    toast_error(format!("重新登录失败：{e:?}"));
+                    set_captcha.set(String::new());
+                    handle_get_captcha(());
+                }
+            }
+            set_relogging_in.set(false);
+        });
+    };
+
+    // 检查内置代理，连上之后再获取验证码
+    let (proxy_unavailable, set_proxy_unavailable) = signal(None::<String>);
+    let (log_file, set_log_file) = signal(None::<String>);
+    let check_proxy = move || {
+        spawn_local(async move {
+            match proxy::connect().await {
+                Ok(()) => {
+                    set_proxy_unavailable.set(None);
+                    set_log_file.set(proxy::log_file_path().await);
+                    handle_get_captcha(());
+                }
+                Err(e) => {
+                    let error_msg = e.to_string();
+                    set_status_message.set(error_msg.clone());
+                    toast_error(error_msg.clone());
+                    set_proxy_unavailable.set(Some(error_msg));
+                }
+            }
+        });
+    };
+
+    // 初始化时检查代理并获取验证码
+    Effect::new(move |_| {
+        check_proxy();
     });
 
     // 在使用 batch_list 时使用 app_state
@@ -624,6 +1282,19 @@ pub fn App() -> impl IntoView {
                 style="background: linear-gradient(135deg, rgba(0,0,0,0.1), rgba(0,0,0,0.05)), url('./public/91403676_p0_z2.jpg'); background-size: cover; background-position: center 20%; background-attachment: fixed;"
             >
 
+    // 内置代理不可用时的提示
+    {move || proxy_unavailable.get().map(|error_msg| view! {
+        <div class="w-full max-w-sm sm:max-w-md mx-auto mb-4 flex items-center justify-between gap-3 bg-red-600/80 backdrop-blur-sm rounded-xl p-3 border border-red-400/60 text-white text-sm">
+            <span class="break-words">{error_msg}</span>
+            <button
+                class="bg-white/20 hover:bg-white/30 text-white text-xs font-medium py-1.5 px-3 rounded-lg transition-all duration-300 whitespace-nowrap"
+                on:click=move |_| check_proxy()
+            >
+                "重试"
+            </button>
+        </div>
+    })}
+
     {/* Logo和标题 - 独立的小卡片 */}
     <div class="text-center mb-6 bg-black/30 backdrop-blur-sm rounded-2xl p-3 border border-white/20"
         class:hidden={move || step.get() != 1}
@@ -644,6 +1315,11 @@ pub fn App() -> impl IntoView {
                 </svg>
                 <span>"喜欢的话就戳我去点颗星吧～(∠・ω< )⌒☆"</span>
             </ExternalLink>
+            {move || log_file.get().map(|path| view! {
+                <p class="mt-1 text-white/50 text-[10px] break-all select-all">
+                    "反馈问题时请附上日志文件: " {path}
+                </p>
+            })}
             // <a
             //     href="https://github.com/Islatri/funky-lesson"
             //     target="_blank"
@@ -807,6 +1483,134 @@ pub fn App() -> impl IntoView {
                                 <span class="text-blue-300 font-bold text-lg">
                                     {move || app_state.get().enrollment_status.get().total_requests}
                                 </span>
+                                <span class="text-white/80 text-sm ml-4">"已选: "</span>
+                                <span class="text-green-300 font-bold text-lg">
+                                    {move || {
+                                        let snapshot = app_state.get().enrollment_status.get();
+                                        let selected = snapshot
+                                            .courses
+                                            .iter()
+                                            .filter(|c| matches!(c.status, CourseStatus::Selected | CourseStatus::AlreadySelected))
+                                            .count();
+                                        format!("{selected}/{}", snapshot.courses.len())
+                                    }}
+                                </span>
+                                <div class="mt-2 flex flex-wrap items-center justify-center gap-x-4 gap-y-1 text-xs text-white/70">
+                                    {move || {
+                                        let backoff = app_state.get().enrollment_status.get().backoff;
+                                        let overloaded = (backoff.overloaded > 0).then(|| view! {
+                                            <span class="text-red-300">
+                                                {format!("服务器过载，已连续 {} 次", backoff.overloaded)}
+                                            </span>
+                                        });
+                                        view! {
+                                            <span>{format!("请求间隔: {}ms", backoff.delay_ms)}</span>
+                                            {overloaded}
+                                        }
+                                    }}
+                                    <label class="flex items-center gap-1">
+                                        "每秒请求上限:"
+                                        <input
+                                            type="number"
+                                            min="0"
+                                            class="w-14 bg-white/10 border border-white/20 rounded px-1 text-white text-xs"
+                                            prop:value=move || max_rps.get().to_string()
+                                            on:change=move |ev| {
+                                                set_max_rps.set(event_target_value(&ev).parse().unwrap_or(0));
+                                            }
+                                            disabled=move || is_enrolling.get()
+                                        />
+                                        <span class="text-white/50">"(0 为不限)"</span>
+                                    </label>
+                                </div>
+                                <div class="mt-2 flex flex-wrap items-center justify-center gap-2 text-xs text-white/70">
+                                    <label class="flex items-center gap-1">
+                                        <input
+                                            type="checkbox"
+                                            prop:checked=move || watch_mode.get()
+                                            on:change=move |ev| set_watch_mode.set(event_target_checked(&ev))
+                                            disabled=move || is_enrolling.get()
+                                        />
+                                        "盯课模式"
+                                    </label>
+                                    <label class="flex items-center gap-1">
+                                        "每"
+                                        <input
+                                            type="number"
+                                            min="1"
+                                            class="w-14 bg-white/10 border border-white/20 rounded px-1 text-white text-xs"
+                                            prop:value=move || watch_secs.get().to_string()
+                                            on:change=move |ev| {
+                                                set_watch_secs.set(event_target_value(&ev).parse().unwrap_or(10).max(1));
+                                            }
+                                            disabled=move || is_enrolling.get() || !watch_mode.get()
+                                        />
+                                        "秒查询一次余量"
+                                    </label>
+                                    <span class="text-white/50">"(有人退课空出名额时才发送选课请求，适合长时间挂着)"</span>
+                                </div>
+                                <div class="mt-2 flex flex-wrap items-center justify-center gap-2 text-xs text-white/70">
+                                    <label class="flex items-center gap-1">
+                                        "定时开始:"
+                                        <input
+                                            type="datetime-local"
+                                            step="1"
+                                            class="bg-white/10 border border-white/20 rounded px-1 text-white text-xs"
+                                            prop:value=move || start_at.get().map(format_datetime_local).unwrap_or_default()
+                                            on:change=move |ev| set_start_at.set(parse_datetime_local(&event_target_value(&ev)))
+                                            disabled=move || is_enrolling.get()
+                                        />
+                                    </label>
+                                    {move || {
+                                        batch_begin_time(&app_state.get()).map(|begin| view! {
+                                            <button
+                                                type="button"
+                                                class="bg-blue-500/60 hover:bg-blue-600/60 text-white rounded px-2 py-0.5 disabled:opacity-50"
+                                                on:click=move |_| set_start_at.set(Some(begin))
+                                                disabled=move || is_enrolling.get()
+                                            >
+                                                "使用批次开始时间"
+                                            </button>
+                                        })
+                                    }}
+                                    <Show when=move || start_at.get().is_some()>
+                                        <button
+                                            type="button"
+                                            class="bg-white/10 hover:bg-white/20 text-white rounded px-2 py-0.5 disabled:opacity-50"
+                                            on:click=move |_| set_start_at.set(None)
+                                            disabled=move || is_enrolling.get()
+                                        >
+                                            "取消定时"
+                                        </button>
+                                    </Show>
+                                </div>
+                                // 定时开始的倒计时
+                                {move || {
+                                    let snapshot = app_state.get().enrollment_status.get();
+                                    let Phase::Scheduled { at_ms } = snapshot.phase else {
+                                        return None;
+                                    };
+                                    let server_now = snapshot.clock.server_time(now_ms.get());
+                                    let clock = match (snapshot.clock.offset_ms(), snapshot.clock.uncertainty_ms()) {
+                                        (Some(offset), Some(uncertainty)) => format!(
+                                            "服务器时间 {} · 本地时钟{} {}ms · 误差 ±{}ms",
+                                            format_time(server_now),
+                                            if offset >= 0 { "慢" } else { "快" },
+                                            offset.abs(),
+                                            uncertainty
+                                        ),
+                                        _ => "正在校准服务器时钟...".to_string(),
+                                    };
+                                    Some(view! {
+                                        <div class="mt-2">
+                                            <span class="text-white/80 text-sm">"距离开始: "</span>
+                                            <span class="text-yellow-200 font-bold text-lg font-mono">
+                                                {format_countdown(at_ms.saturating_sub(server_now))}
+                                            </span>
+                                            <div class="text-xs text-white/50">{clock}</div>
+                                        </div>
+                                    })
+                                }}
                             </div>
                         </div>
 
@@ -814,11 +1618,21 @@ pub fn App() -> impl IntoView {
                         <div class="bg-black/80 backdrop-blur-sm text-green-400 p-4 rounded-xl h-48 sm:h-64 overflow-y-auto font-mono border border-white/20">
                             <div class="text-xs text-white/60 mb-2 uppercase tracking-wide">"实时状态"</div>
                             <For
-                                each=move || app_state.get().enrollment_status.get().course_statuses
-                                key=|status| status.clone()
-                                children=move |status| {
+                                each=move || {
+                                    app_state
+                                        .get()
+                                        .enrollment_status
+                                        .get()
+                                        .courses
+                                        .into_iter()
+                                        .enumerate()
+                                        .map(|(index, state)| (index, state.course.id))
+                                        .collect::<Vec<_>>()
+                                }
+                                key=|(index, id)| (*index, id.clone())
+                                children=move |(index, _)| {
                                     view! {
-                                        <div class="whitespace-pre-wrap text-sm leading-relaxed">{status}</div>
+                                        <CourseStateRow index=index enrollment=app_state.get().enrollment_status/>
                                     }
                                 }
                             />
@@ -831,14 +1645,22 @@ pub fn App() -> impl IntoView {
                                 on:click=handle_enroll
                                 disabled=move || is_enrolling.get()
                             >
-                                "🚀 开始抢课"
+                                {move || {
+                                    let server_now = app_state.get().enrollment_status.get().clock.server_time(now_ms.get());
+                                    let scheduled = start_at.get().is_some_and(|ms| ms > server_now);
+                                    match (scheduled, watch_mode.get()) {
+                                        (true, _) => "⏰ 定时开始",
+                                        (false, true) => "👀 开始盯课",
+                                        (false, false) => "🚀 开始抢课",
+                                    }
+                                }}
                             </button>
                             <button
                                 class="bg-red-500/80 hover:bg-red-600/80 text-white font-medium py-3 px-6 rounded-lg transition-all duration-300 focus:outline-none focus:ring-2 focus:ring-red-400 disabled:opacity-50 disabled:cursor-not-allowed"
                                 on:click=handle_stop_enroll
-                                disabled=move || !is_enrolling.get()
+                                disabled=move || !is_enrolling.get() || stopping.get()
                             >
-                                "⏹️ 停止抢课"
+                                {move || if stopping.get() { "⏳ 正在停止..." } else { "⏹️ 停止抢课" }}
                             </button>
                         </div>
 
@@ -873,9 +1695,17 @@ pub fn App() -> impl IntoView {
                             <div class="bg-black/30 backdrop-blur-sm rounded-xl p-4 border border-white/20">
                                 <div class="flex items-center gap-2 mb-3">
                                     <div class="w-3 h-3 bg-blue-400 rounded-full"></div>
-                                    <h3 class="text-lg font-bold text-white">"待选课程（即收藏课程）"</h3>
+                                    <h3 class="text-lg font-bold text-white">"待选课程"</h3>
+                                    <span class="text-white/50 text-xs">"同组课程按志愿顺序尝试，选上一门即停止其余"</span>
                                     <span class="text-white/70 text-sm">
-                                        {move || format!("共 {} 门", app_state.get().favorite_courses.get().len())}
+                                        {move || {
+                                            let state = app_state.get();
+                                            format!(
+                                                "参与 {}/{} 门",
+                                                included_count(&state),
+                                                state.favorite_courses.get().len(),
+                                            )
+                                        }}
                                     </span>
                                 </div>
                                 <div class="space-y-2 max-h-40 overflow-y-auto">
@@ -890,9 +1720,25 @@ pub fn App() -> impl IntoView {
                                                             <div class="font-medium text-white text-sm">{course.KCM}</div>
                                                             <div class="text-xs text-white/70 mt-1">
                                                                 {format!("教师: {} | ID: {}", course.SKJS, course.JXBID)}
+                                                                <FavoriteActions
+                                                                    course=course.clone()
+                                                                    app_state=app_state.get_untracked()
+                                                                    disabled=is_enrolling
+                                                                />
                                                             </div>
                                                         </div>
+                                                        <GroupPicker
+                                                            id=course.JXBID.clone()
+                                                            app_state=app_state.get_untracked()
+                                                            disabled=is_enrolling
+                                                        />
                                                     </div>
+                                                    <PolicyEditor
+                                                        id=course.JXBID.clone()
+                                                        app_state=app_state.get_untracked()
+                                                        disabled=is_enrolling
+                                                        watch_mode=watch_mode
+                                                    />
                                                 </div>
                                             }
                                         }
@@ -900,9 +1746,150 @@ pub fn App() -> impl IntoView {
                                 </div>
                             </div>
                         </div>
+
+                        // 已选和待选课程的周课表
+                        <Timetable app_state=app_state.get_untracked()/>
+
+                        // 课程目录
+                        <CatalogBrowser app_state=app_state.get_untracked() disabled=is_enrolling/>
                     </div>
                 </div>
 
+                // 开始前发现重复或时间冲突时的确认窗口
+                <Show when=move || confirming.get()>
+                    <div class="fixed inset-0 z-40 flex items-center justify-center bg-black/60 backdrop-blur-sm p-4">
+                        <div class="w-full max-w-lg bg-black/70 border border-white/20 rounded-xl p-4 space-y-3">
+                            <h3 class="text-base font-semibold text-white">"开始前请确认"</h3>
+                            <p class="text-xs text-white/70">
+                                "以下课程已经选上或上课时间冲突，可以移出本次抢课，也可以确认后照常开始"
+                            </p>
+                            <div class="space-y-2 max-h-72 overflow-y-auto">
+                                <For
+                                    each=move || issues.get()
+                                    key=|issue: &Issue| (issue.id.clone(), issue.kind.to_string())
+                                    children=move |issue: Issue| {
+                                        let id = StoredValue::new(issue.id.clone());
+                                        let exclude = move |_| {
+                                            id.with_value(|id| {
+                                                app_state
+                                                    .get_untracked()
+                                                    .update_plan(|plan| plan.update_course(id, |course| course.include = false))
+                                            })
+                                        };
+                                        view! {
+                                            <div class="flex items-center justify-between gap-2 p-2 bg-yellow-500/10 border border-yellow-400/30 rounded-lg">
+                                                <div class="text-sm">
+                                                    <span class="text-white">{issue.name.clone()}</span>
+                                                    <span class="ml-2 text-xs text-yellow-200">{issue.kind.to_string()}</span>
+                                                </div>
+                                                <button
+                                                    type="button"
+                                                    class="shrink-0 text-xs bg-white/10 hover:bg-white/20 text-white rounded px-2 py-1"
+                                                    on:click=exclude
+                                                >
+                                                    "移出本次抢课"
+                                                </button>
+                                            </div>
+                                        }
+                                    }
+                                />
+                                <Show when=move || issues.with(Vec::is_empty)>
+                                    <div class="text-sm text-green-300">"问题都已处理"</div>
+                                </Show>
+                            </div>
+                            <div class="flex justify-end gap-2">
+                                <button
+                                    type="button"
+                                    class="bg-white/10 hover:bg-white/20 text-white text-sm rounded-lg px-4 py-2"
+                                    on:click=move |_| set_confirming.set(false)
+                                >
+                                    "取消"
+                                </button>
+                                <button
+                                    type="button"
+                                    class="bg-green-500/80 hover:bg-green-600/80 text-white text-sm rounded-lg px-4 py-2"
+                                    on:click=handle_confirm_enroll
+                                >
+                                    {move || if issues.with(Vec::is_empty) { "开始" } else { "仍然开始" }}
+                                </button>
+                            </div>
+                        </div>
+                    </div>
+                </Show>
+
+                // 登录失效时的重新登录窗口
+                <Show when=move || session_expired.get()>
+                    <div class="fixed inset-0 z-40 flex items-center justify-center bg-black/60 backdrop-blur-sm p-4">
+                        <form
+                            class="w-full max-w-sm bg-black/70 border border-white/20 rounded-xl p-4 space-y-3"
+                            on:submit=handle_relogin
+                        >
+                            <h3 class="text-base font-semibold text-white">"登录已失效"</h3>
+                            <p class="text-xs text-white/70">
+                                "抢课已暂停，重新登录后会恢复原来的批次并从当前进度继续"
+                            </p>
+                            <div>
+                                <label class="block text-xs font-medium text-white/80 mb-2">"学号"</label>
+                                <input
+                                    class="w-full px-3 py-2 bg-white/5 border border-white/10 rounded-lg text-white/70 text-sm"
+                                    type="text"
+                                    readonly
+                                    prop:value=move || username.get()
+                                />
+                            </div>
+                            <div>
+                                <label class="block text-xs font-medium text-white/80 mb-2">"密码"</label>
+                                <input
+                                    class="w-full px-3 py-2 bg-white/10 border border-white/20 rounded-lg text-white text-sm placeholder-white/50 focus:outline-none focus:ring-2 focus:ring-blue-400 focus:border-transparent transition-all duration-300"
+                                    type="password"
+                                    prop:value=move || password.get()
+                                    on:input=move |ev| set_password.set(event_target_value(&ev))
+                                />
+                            </div>
+                            <div>
+                                <label class="block text-xs font-medium text-white/80 mb-2">"验证码"</label>
+                                <div class="flex items-center gap-2">
+                                    <input
+                                        type="text"
+                                        class="w-full px-3 py-2 bg-white/10 border border-white/20 rounded-lg text-white text-sm placeholder-white/50 focus:outline-none focus:ring-2 focus:ring-blue-400 focus:border-transparent transition-all duration-300"
+                                        placeholder="请输入验证码"
+                                        prop:value=move || captcha.get()
+                                        on:input=move |ev| set_captcha.set(event_target_value(&ev))
+                                    />
+                                    <img
+                                        src={move || captcha_image_src.get()}
+                                        alt="验证码"
+                                        class="h-8 border border-white/20 rounded flex-shrink-0"
+                                    />
+                                    <button
+                                        type="button"
+                                        class="bg-green-500/80 hover:bg-green-600/80 text-white text-xs font-medium py-1.5 px-3 rounded-lg transition-all duration-300 whitespace-nowrap"
+                                        on:click=move |_| handle_get_captcha(())
+                                    >
+                                        "刷新"
+                                    </button>
+                                </div>
+                            </div>
+                            <div class="flex gap-2 pt-1">
+                                <button
+                                    type="submit"
+                                    class="flex-1 bg-blue-500/80 hover:bg-blue-600/80 disabled:opacity-50 text-white text-sm font-medium py-2 rounded-lg transition-all duration-300"
+                                    disabled=move || relogging_in.get()
+                                >
+                                    {move || if relogging_in.get() { "登录中..." } else { "重新登录" }}
+                                </button>
+                                <button
+                                    type="button"
+                                    class="flex-1 bg-red-500/80 hover:bg-red-600/80 text-white text-sm font-medium py-2 rounded-lg transition-all duration-300"
+                                    on:click=handle_stop_enroll
+                                >
+                                    "停止抢课"
+                                </button>
+                            </div>
+                        </form>
+                    </div>
+                </Show>
+
                 // Toast Container
                 <ToastContainer />
             </div>
@@ -0,0 +1,639 @@
+use crate::app::{AppState, toast_error, toast_success, toast_warning};
+use crate::proxy;
+use crate::schedule::{self, ClassDetails};
+use funky_lesson_core::model::structs::CourseInfo;
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+use leptos::web_sys;
+use serde_json::Value;
+
+// 上游的教学班类型，查询课程列表时必须指定
+const CLASS_TYPES: [(&str, &str); 6] = [
+    ("TJKC", "推荐课程"),
+    ("FANKC", "方案内课程"),
+    ("FAWKC", "方案外课程"),
+    ("CXKC", "重修课程"),
+    ("TYKC", "体育课程"),
+    ("XGKC", "校公选课"),
+];
+
+const PAGE_SIZES: [u32; 4] = [10, 20, 50, 100];
+// 筛选时一次取回全部结果，每页取上游允许的最大数量，最多取这么多页
+const FETCH_ALL_PAGE_SIZE: u32 = 100;
+const FETCH_ALL_MAX_PAGES: u32 = 30;
+
+// 课程列表中的一个教学班
+#[derive(Clone)]
+pub struct CatalogEntry {
+    pub course: CourseInfo,
+    pub campus: String,
+    pub details: ClassDetails,
+    pub capacity: Option<u32>,
+    pub enrolled: Option<u32>,
+    // 上游返回的原始数据，加入待选时保存
+    pub raw: Value,
+}
+
+impl CatalogEntry {
+    pub fn from_value(raw: Value) -> Option<Self> {
+        let course = match serde_json::from_value(raw.clone()) {
+            Ok(course) => course,
+            Err(e) => {
+                log::warn!("Ignoring unrecognized class: {e}");
+                return None;
+            }
+        };
+        Some(Self {
+            course,
+            campus: raw["XQ"].as_str().unwrap_or_default().to_string(),
+            details: ClassDetails::from_value(&raw),
+            capacity: count(&raw["KRL"]),
+            enrolled: count(&raw["YXRS"]),
+            raw,
+        })
+    }
+}
+
+// 展开课程列表，按课程分组返回时每门课程下的教学班在 tcList 中
+// 教学班缺少的课程号、课程名等字段从所属课程补上
+pub fn flatten(rows: &Value) -> Vec<Value> {
+    let mut classes = Vec::new();
+    for row in rows.as_array().into_iter().flatten() {
+        if row.get("JXBID").is_some() {
+            classes.push(row.clone());
+            continue;
+        }
+        for class in flatten(&row["tcList"]) {
+            let mut class = class;
+            if let (Some(class_fields), Some(course_fields)) =
+                (class.as_object_mut(), row.as_object())
+            {
+                for (key, value) in course_fields {
+                    if key != "tcList" && !class_fields.contains_key(key) {
+                        class_fields.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            classes.push(class);
+        }
+    }
+    classes
+}
+
+// 人数字段有时以字符串返回
+pub fn count(value: &Value) -> Option<u32> {
+    value
+        .as_u64()
+        .and_then(|n| u32::try_from(n).ok())
+        .or_else(|| value.as_str()?.trim().parse().ok())
+}
+
+// 从课程列表响应中取出教学班和结果总数
+fn rows_of(json: Value) -> Result<(Vec<CatalogEntry>, u32), String> {
+    if json["code"] != 200 {
+        return Err(json["msg"].as_str().unwrap_or("未知错误").to_string());
+    }
+    let data = &json["data"];
+    let rows = flatten(&data["rows"])
+        .into_iter()
+        .filter_map(CatalogEntry::from_value)
+        .collect();
+    Ok((rows, count(&data["total"]).unwrap_or(0)))
+}
+
+// 筛选条件，条件为空时不限制
+// 上游不支持这些条件，设置后先取回全部查询结果再在本地筛选和分页
+#[derive(Debug, Clone, Default)]
+struct Filters {
+    teacher: String,
+    campus: String,
+    weekday: Option<u8>,
+    period: Option<u8>,
+}
+
+impl Filters {
+    fn is_empty(&self) -> bool {
+        self.teacher.trim().is_empty()
+            && self.campus.trim().is_empty()
+            && self.weekday.is_none()
+            && self.period.is_none()
+    }
+
+    fn matches(&self, entry: &CatalogEntry) -> bool {
+        let teacher = self.teacher.trim();
+        let campus = self.campus.trim();
+        (teacher.is_empty() || entry.course.SKJS.contains(teacher))
+            && (campus.is_empty() || entry.campus.contains(campus))
+            && ((self.weekday.is_none() && self.period.is_none())
+                || entry.details.sessions.iter().any(|session| {
+                    self.weekday.is_none_or(|day| session.weekday == day)
+                        && self.period.is_none_or(|period| session.has_period(period))
+                }))
+    }
+}
+
+// 课程目录：分页查询可选课程，直接加入待选课程
+#[component]
+pub fn CatalogBrowser(app_state: AppState, disabled: ReadSignal<bool>) -> impl IntoView {
+    let class_type = RwSignal::new(CLASS_TYPES[0].0.to_string());
+    let keyword = RwSignal::new(String::new());
+    let page_size = RwSignal::new(PAGE_SIZES[1]);
+    let page = RwSignal::new(1_u32);
+    let total = RwSignal::new(0_u32);
+    let entries = RwSignal::new(Vec::<CatalogEntry>::new());
+    let loading = RwSignal::new(false);
+    let filters = RwSignal::new(Filters::default());
+
+    // 已经取回全部查询结果，此时在本地筛选和分页
+    let loaded_all = RwSignal::new(false);
+
+    let query = move || {
+        let token = app_state.token.get_untracked()?;
+        let batch_id = app_state.batch_id.get_untracked()?;
+        Some((
+            token,
+            batch_id,
+            class_type.get_untracked(),
+            keyword.get_untracked(),
+        ))
+    };
+
+    // 取回全部查询结果，用于筛选
+    let search_all = move || {
+        let Some((token, batch_id, class_type, keyword)) = query() else {
+            return;
+        };
+        loading.set(true);
+        spawn_local(async move {
+            let mut all = Vec::new();
+            let mut count = 0;
+            for page_number in 1..=FETCH_ALL_MAX_PAGES {
+                let result = proxy::get_class_list(
+                    &token,
+                    &batch_id,
+                    &class_type,
+                    keyword.trim(),
+                    page_number,
+                    FETCH_ALL_PAGE_SIZE,
+                    |_| {},
+                )
+                .await;
+                match result.map_err(|e| e.to_string()).and_then(rows_of) {
+                    Ok((rows, rows_total)) => {
+                        let done = rows.is_empty();
+                        all.extend(rows);
+                        count = rows_total;
+                        if done || all.len() as u32 >= count {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        toast_error(format!("查询课程列表失败：{e}"));
+                        loading.set(false);
+                        return;
+                    }
+                }
+            }
+            if (all.len() as u32) < count {
+                toast_warning(format!(
+                    "结果太多，只在前 {} 个教学班中筛选，请用关键词缩小范围",
+                    all.len()
+                ));
+            }
+            entries.set(all);
+            total.set(count);
+            page.set(1);
+            loaded_all.set(true);
+            loading.set(false);
+        });
+    };
+
+    // 按上游分页查询一页
+    let search_page = move |page_number: u32| {
+        let Some((token, batch_id, class_type, keyword)) = query() else {
+            return;
+        };
+        loading.set(true);
+        spawn_local(async move {
+            let result = proxy::get_class_list(
+                &token,
+                &batch_id,
+                &class_type,
+                keyword.trim(),
+                page_number,
+                page_size.get_untracked(),
+                |_| {},
+            )
+            .await;
+            match result.map_err(|e| e.to_string()).and_then(rows_of) {
+                Ok((rows, count)) => {
+                    // 一页就是全部结果时同样可以在本地筛选
+                    loaded_all.set(page_number == 1 && rows.len() as u32 >= count);
+                    entries.set(rows);
+                    total.set(count);
+                    page.set(page_number);
+                }
+                Err(e) => toast_error(format!("查询课程列表失败：{e}")),
+            }
+            loading.set(false);
+            // 查询期间设置了筛选条件时 update_filters 没有取回全部结果，在这里补上
+            if !loaded_all.get_untracked()
+                && total.get_untracked() > 0
+                && !filters.with_untracked(Filters::is_empty)
+            {
+                search_all();
+            }
+        });
+    };
+
+    let search = move || {
+        if filters.with_untracked(Filters::is_empty) {
+            search_page(1);
+        } else {
+            search_all();
+        }
+    };
+
+    // 设置筛选条件时还没有取回全部结果，先取回再筛选
+    // 正在查询一页时由 search_page 在结束后取回
+    let update_filters = move |f: &dyn Fn(&mut Filters)| {
+        filters.update(|filters| f(filters));
+        if loaded_all.get_untracked() {
+            page.set(1);
+        } else if !loading.get_untracked()
+            && total.get_untracked() > 0
+            && !filters.with_untracked(Filters::is_empty)
+        {
+            search_all();
+        }
+    };
+
+    let matched = Memo::new(move |_| {
+        let filters = filters.get();
+        entries.with(|entries| {
+            entries
+                .iter()
+                .filter(|entry| filters.matches(entry))
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+    });
+    // 本地分页时的结果总数，否则为上游的结果总数
+    let result_count = move || {
+        if loaded_all.get() {
+            matched.with(Vec::len) as u32
+        } else {
+            total.get()
+        }
+    };
+    let pages = move || result_count().div_ceil(page_size.get()).max(1);
+    let shown = Memo::new(move |_| {
+        let all = matched.get();
+        if !loaded_all.get() {
+            return all;
+        }
+        let size = page_size.get() as usize;
+        all.into_iter()
+            .skip((page.get() as usize - 1) * size)
+            .take(size)
+            .collect()
+    });
+    let go_to = move |page_number: u32| {
+        if loaded_all.get_untracked() {
+            page.set(page_number);
+        } else {
+            search_page(page_number);
+        }
+    };
+
+    let add = move |entry: CatalogEntry| {
+        let id = entry.course.JXBID.clone();
+        if app_state
+            .favorite_courses
+            .with_untracked(|courses| courses.iter().any(|c| c.JXBID == id))
+        {
+            return;
+        }
+        app_state.update_plan(|plan| plan.add(entry.raw.clone()));
+        app_state
+            .class_details
+            .update(|details| _ = details.insert(id, entry.details.clone()));
+        app_state
+            .favorite_courses
+            .update(|courses| courses.push(entry.course.clone()));
+        toast_success(format!("已加入待选：{}", entry.course.KCM));
+    };
+
+    let input_class = "bg-white/10 border border-white/20 rounded px-2 py-1 text-white text-xs";
+
+    view! {
+        <div class="bg-black/30 backdrop-blur-sm rounded-xl p-4 border border-white/20 mt-4">
+            <div class="flex items-center gap-2 mb-3">
+                <div class="w-3 h-3 bg-purple-400 rounded-full"></div>
+                <h3 class="text-lg font-bold text-white">"课程目录"</h3>
+                <span class="text-white/50 text-xs">"不用先去选课网站收藏，查到的教学班可以直接加入待选"</span>
+            </div>
+            <form
+                class="flex flex-wrap items-center gap-2 text-xs text-white/80"
+                on:submit=move |ev: web_sys::SubmitEvent| {
+                    ev.prevent_default();
+                    search();
+                }
+            >
+                <select
+                    class=input_class
+                    on:change=move |ev| class_type.set(event_target_value(&ev))
+                >
+                    {CLASS_TYPES
+                        .into_iter()
+                        .map(|(code, name)| {
+                            view! {
+                                <option value=code selected=move || class_type.get() == code>
+                                    {name}
+                                </option>
+                            }
+                        })
+                        .collect_view()}
+                </select>
+                <input
+                    type="text"
+                    class=input_class
+                    placeholder="课程名 / 课程号 / 教师"
+                    prop:value=move || keyword.get()
+                    on:input=move |ev| keyword.set(event_target_value(&ev))
+                />
+                <select
+                    class=input_class
+                    on:change=move |ev| {
+                        page_size.set(event_target_value(&ev).parse().unwrap_or(PAGE_SIZES[1]));
+                        // 按上游分页时 entries 还是按旧的每页条数取回的，需要重新查询第一页
+                        if loaded_all.get_untracked() {
+                            page.set(1);
+                        } else if total.get_untracked() > 0 {
+                            search_page(1);
+                        }
+                    }
+                >
+                    {PAGE_SIZES
+                        .into_iter()
+                        .map(|size| {
+                            view! {
+                                <option value=size.to_string() selected=move || page_size.get() == size>
+                                    {format!("每页 {size} 条")}
+                                </option>
+                            }
+                        })
+                        .collect_view()}
+                </select>
+                <button
+                    type="submit"
+                    class="bg-purple-500/70 hover:bg-purple-600/70 text-white rounded px-3 py-1 disabled:opacity-50"
+                    disabled=move || loading.get()
+                >
+                    {move || if loading.get() { "查询中..." } else { "查询" }}
+                </button>
+            </form>
+            <div class="flex flex-wrap items-center gap-2 mt-2 text-xs text-white/80">
+                <span class="text-white/50">"筛选（在全部查询结果中）:"</span>
+                <input
+                    type="text"
+                    class=input_class
+                    placeholder="教师"
+                    on:change=move |ev| {
+                        let teacher = event_target_value(&ev);
+                        update_filters(&|f| f.teacher = teacher.clone());
+                    }
+                />
+                <input
+                    type="text"
+                    class=input_class
+                    placeholder="校区"
+                    on:change=move |ev| {
+                        let campus = event_target_value(&ev);
+                        update_filters(&|f| f.campus = campus.clone());
+                    }
+                />
+                <select
+                    class=input_class
+                    on:change=move |ev| {
+                        let weekday = event_target_value(&ev).parse().ok();
+                        update_filters(&|f| f.weekday = weekday);
+                    }
+                >
+                    <option value="">"任意星期"</option>
+                    {(1..=7_u8)
+                        .map(|day| view! { <option value=day.to_string()>{schedule::weekday_name(day)}</option> })
+                        .collect_view()}
+                </select>
+                <input
+                    type="number"
+                    min="1"
+                    class=format!("w-20 {input_class}")
+                    placeholder="第几节"
+                    on:change=move |ev| {
+                        let period = event_target_value(&ev).parse().ok();
+                        update_filters(&|f| f.period = period);
+                    }
+                />
+            </div>
+            <div class="space-y-2 max-h-72 overflow-y-auto mt-3">
+                <For
+                    each=move || shown.get()
+                    key=|entry| entry.course.JXBID.clone()
+                    children=move |entry| {
+                        let id = entry.course.JXBID.clone();
+                        let added = Memo::new(move |_| {
+                            app_state.favorite_courses.with(|courses| courses.iter().any(|c| c.JXBID == id))
+                        });
+                        let seats = match (entry.capacity, entry.enrolled) {
+                            (Some(capacity), Some(enrolled)) => format!("已选 {enrolled}/{capacity}"),
+                            _ => String::new(),
+                        };
+                        let details = [entry.course.SKJS.as_str(), entry.campus.as_str(), entry.details.schedule.as_str(), seats.as_str()]
+                            .into_iter()
+                            .filter(|detail| !detail.is_empty())
+                            .collect::<Vec<_>>()
+                            .join(" | ");
+                        let name = entry.course.KCM.clone();
+                        let entry = StoredValue::new(entry);
+                        view! {
+                            <div class="p-3 bg-purple-500/20 border border-purple-400/30 rounded-lg flex items-center justify-between gap-2">
+                                <div class="flex-1">
+                                    <div class="font-medium text-white text-sm">{name}</div>
+                                    <div class="text-xs text-white/70 mt-1">{details}</div>
+                                </div>
+                                <button
+                                    type="button"
+                                    class="bg-blue-500/60 hover:bg-blue-600/60 text-white text-xs rounded px-2 py-1 disabled:opacity-50"
+                                    on:click=move |_| add(entry.get_value())
+                                    disabled=move || added.get() || disabled.get()
+                                >
+                                    {move || if added.get() { "已在待选" } else { "加入待选" }}
+                                </button>
+                            </div>
+                        }
+                    }
+                />
+            </div>
+            <div class="flex items-center justify-center gap-3 mt-3 text-xs text-white/80">
+                <button
+                    type="button"
+                    class="bg-white/10 hover:bg-white/20 rounded px-2 py-1 disabled:opacity-50"
+                    on:click=move |_| go_to(page.get_untracked() - 1)
+                    disabled=move || loading.get() || page.get() <= 1
+                >
+                    "上一页"
+                </button>
+                <span>
+                    {move || {
+                        if loaded_all.get() && !filters.with(Filters::is_empty) {
+                            format!(
+                                "第 {}/{} 页 · 筛选出 {} 个教学班（共 {} 个）",
+                                page.get(),
+                                pages(),
+                                result_count(),
+                                entries.with(Vec::len),
+                            )
+                        } else {
+                            format!("第 {}/{} 页 · 共 {} 个教学班", page.get(), pages(), result_count())
+                        }
+                    }}
+                </span>
+                <button
+                    type="button"
+                    class="bg-white/10 hover:bg-white/20 rounded px-2 py-1 disabled:opacity-50"
+                    on:click=move |_| go_to(page.get_untracked() + 1)
+                    disabled=move || loading.get() || page.get() >= pages()
+                >
+                    "下一页"
+                </button>
+            </div>
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn class(id: &str, teacher: &str, campus: &str, schedule: &str) -> Value {
+        json!({
+            "JXBID": id,
+            "KCM": "高等数学",
+            "SKJS": teacher,
+            "KCH": "A01",
+            "XQ": campus,
+            "YPSJDD": schedule,
+            "teachingClassType": "TJKC",
+            "secretVal": "",
+        })
+    }
+
+    fn entry(class: Value) -> CatalogEntry {
+        CatalogEntry::from_value(class).unwrap()
+    }
+
+    #[test]
+    fn flatten_inherits_course_fields() {
+        let rows = json!([
+            class("1", "张老师", "前卫", ""),
+            {
+                "KCH": "B01",
+                "KCM": "大学物理",
+                "XQ": "南岭",
+                "tcList": [
+                    { "JXBID": "2", "SKJS": "李老师", "XQ": "前卫" },
+                    { "JXBID": "3", "SKJS": "王老师" },
+                ],
+            },
+        ]);
+        let classes = flatten(&rows);
+        assert_eq!(classes.len(), 3);
+        assert_eq!(classes[0]["KCM"], "高等数学");
+        // 教学班自己的字段不被课程覆盖
+        assert_eq!(
+            (&classes[1]["JXBID"], &classes[1]["KCM"], &classes[1]["XQ"]),
+            (&json!("2"), &json!("大学物理"), &json!("前卫"))
+        );
+        assert_eq!(
+            (&classes[2]["KCH"], &classes[2]["XQ"]),
+            (&json!("B01"), &json!("南岭"))
+        );
+        assert!(classes[2].get("tcList").is_none());
+        assert!(flatten(&Value::Null).is_empty());
+    }
+
+    #[test]
+    fn count_accepts_numbers_and_strings() {
+        assert_eq!(count(&json!(12)), Some(12));
+        assert_eq!(count(&json!("12")), Some(12));
+        assert_eq!(count(&json!(" 12 ")), Some(12));
+        assert_eq!(count(&json!("")), None);
+        assert_eq!(count(&json!(-1)), None);
+        assert_eq!(count(&Value::Null), None);
+    }
+
+    #[test]
+    fn rows_of_reads_classes_and_total() {
+        let json = json!({
+            "code": 200,
+            "data": {
+                "total": "21",
+                "rows": [class("1", "张老师", "前卫", ""), { "KCM": "无法识别" }],
+            },
+        });
+        let (rows, total) = rows_of(json).unwrap();
+        assert_eq!(total, 21);
+        assert_eq!(
+            rows.iter()
+                .map(|row| row.course.JXBID.as_str())
+                .collect::<Vec<_>>(),
+            ["1"]
+        );
+        assert_eq!(rows[0].campus, "前卫");
+    }
+
+    #[test]
+    fn rows_of_reports_upstream_errors() {
+        let error = rows_of(json!({ "code": 401, "msg": "登录已失效" }));
+        assert_eq!(error.err().as_deref(), Some("登录已失效"));
+        let error = rows_of(json!({ "code": 500 }));
+        assert_eq!(error.err().as_deref(), Some("未知错误"));
+    }
+
+    #[test]
+    fn weekday_and_period_must_match_the_same_session() {
+        let entry = entry(class(
+            "1",
+            "张老师",
+            "前卫",
+            "1-16周 星期一 第1-2节;1-16周 星期三 第5-6节",
+        ));
+        let filters = |weekday, period| Filters {
+            weekday,
+            period,
+            ..Filters::default()
+        };
+        assert!(filters(None, None).matches(&entry));
+        assert!(filters(Some(1), Some(2)).matches(&entry));
+        assert!(filters(Some(3), Some(5)).matches(&entry));
+        assert!(filters(None, Some(6)).matches(&entry));
+        assert!(!filters(Some(1), Some(5)).matches(&entry));
+        assert!(!filters(Some(3), Some(1)).matches(&entry));
+        assert!(!filters(Some(2), None).matches(&entry));
+    }
+
+    #[test]
+    fn teacher_and_campus_filters_match_substrings() {
+        let entry = entry(class("1", "张老师", "前卫校区", "1-16周 星期一 第1-2节"));
+        let filters = |teacher: &str, campus: &str| Filters {
+            teacher: teacher.to_string(),
+            campus: campus.to_string(),
+            ..Filters::default()
+        };
+        assert!(filters(" 张 ", "前卫").matches(&entry));
+        assert!(!filters("李", "").matches(&entry));
+        assert!(!filters("", "南岭").matches(&entry));
+    }
+}
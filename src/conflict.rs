@@ -0,0 +1,241 @@
+use crate::plan::Plan;
+use crate::schedule::ClassDetails;
+use funky_lesson_core::model::structs::CourseInfo;
+use std::collections::HashMap;
+use std::fmt;
+
+// 开始抢课前发现的问题，id 和 name 为参与抢课的教学班
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub id: String,
+    pub name: String,
+    pub kind: IssueKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IssueKind {
+    // 这个教学班已经在已选课程中
+    AlreadySelected,
+    // 已经选了同一门课的其他教学班
+    SameCourseSelected { other: String },
+    // 与已选课程上课时间冲突，when 为第一次冲突的时间
+    ConflictsWithSelected { other: String, when: String },
+    // 与另一门参与抢课的课程上课时间冲突
+    ConflictsWithTarget { other: String, when: String },
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IssueKind::AlreadySelected => write!(f, "已在已选课程中"),
+            IssueKind::SameCourseSelected { other } => {
+                write!(f, "已选了同一门课的其他教学班（{other}）")
+            }
+            IssueKind::ConflictsWithSelected { other, when } => {
+                write!(f, "与已选课程 {other} 时间冲突（{when}）")
+            }
+            IssueKind::ConflictsWithTarget { other, when } => {
+                write!(f, "与待选课程 {other} 时间冲突（{when}）")
+            }
+        }
+    }
+}
+
+// 检查参与抢课的课程，同一"多选一"分组内的课程只会选上一门，不算冲突
+// 已经选上的课程不再检查时间冲突，没有上课时间的课程不参与时间检查
+pub fn check(
+    targets: &[CourseInfo],
+    selected: &[CourseInfo],
+    details: &HashMap<String, ClassDetails>,
+    plan: &Plan,
+) -> Vec<Issue> {
+    let empty = ClassDetails::default();
+    let details_of = |course: &CourseInfo| details.get(&course.JXBID).unwrap_or(&empty);
+    let group_of = |course: &CourseInfo| plan.position(&course.JXBID).map(|(group, _)| group);
+
+    let targets: Vec<&CourseInfo> = targets
+        .iter()
+        .filter(|c| plan.course(&c.JXBID).include)
+        .collect();
+    let mut issues = Vec::new();
+    let mut issue = |course: &CourseInfo, kind| {
+        issues.push(Issue {
+            id: course.JXBID.clone(),
+            name: course.KCM.clone(),
+            kind,
+        })
+    };
+
+    for (index, &target) in targets.iter().enumerate() {
+        let target_details = details_of(target);
+
+        if selected.iter().any(|c| c.JXBID == target.JXBID) {
+            issue(target, IssueKind::AlreadySelected);
+            continue;
+        }
+        if !target_details.code.is_empty()
+            && let Some(other) = selected
+                .iter()
+                .find(|c| details_of(c).code == target_details.code)
+        {
+            issue(
+                target,
+                IssueKind::SameCourseSelected {
+                    other: format!("{} {}", other.KCM, other.SKJS),
+                },
+            );
+        }
+
+        for other in selected {
+            if let Some(session) = target_details.overlap(details_of(other)) {
+                issue(
+                    target,
+                    IssueKind::ConflictsWithSelected {
+                        other: other.KCM.clone(),
+                        when: session.describe(),
+                    },
+                );
+            }
+        }
+
+        // 每对课程只报告一次
+        for &other in &targets[index + 1..] {
+            let group = group_of(target);
+            if group.is_some() && group == group_of(other) {
+                continue;
+            }
+            if let Some(session) = target_details.overlap(details_of(other)) {
+                issue(
+                    target,
+                    IssueKind::ConflictsWithTarget {
+                        other: other.KCM.clone(),
+                        when: session.describe(),
+                    },
+                );
+            }
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    fn class(id: &str, name: &str, code: &str, schedule: &str) -> Value {
+        json!({
+            "JXBID": id,
+            "KCM": name,
+            "SKJS": "教师",
+            "KCH": code,
+            "YPSJDD": schedule,
+            "teachingClassType": "TJKC",
+            "secretVal": "",
+        })
+    }
+
+    // 返回课程列表和按教学班 ID 保存的上课时间
+    fn courses(classes: &[Value]) -> (Vec<CourseInfo>, HashMap<String, ClassDetails>) {
+        let courses = classes
+            .iter()
+            .map(|class| serde_json::from_value(class.clone()).unwrap())
+            .collect();
+        let details = classes
+            .iter()
+            .map(|class| {
+                let id = class["JXBID"].as_str().unwrap().to_string();
+                (id, ClassDetails::from_value(class))
+            })
+            .collect();
+        (courses, details)
+    }
+
+    fn check_all(targets: &[Value], selected: &[Value], plan: &Plan) -> Vec<Issue> {
+        let (targets, mut details) = courses(targets);
+        let (selected, selected_details) = courses(selected);
+        details.extend(selected_details);
+        check(&targets, &selected, &details, plan)
+    }
+
+    #[test]
+    fn reports_a_class_that_is_already_selected() {
+        let math = [class("1", "高等数学", "A01", "1-16周 星期一 第1-2节")];
+        let issues = check_all(&math, &math, &Plan::default());
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].id, "1");
+        assert_eq!(issues[0].kind, IssueKind::AlreadySelected);
+    }
+
+    #[test]
+    fn reports_another_class_of_the_same_course() {
+        let target = class("2", "高等数学", "A01", "1-16周 星期二 第1-2节");
+        let selected = class("1", "高等数学", "A01", "1-16周 星期一 第1-2节");
+        let issues = check_all(&[target], &[selected], &Plan::default());
+        assert_eq!(
+            issues.iter().map(|issue| &issue.kind).collect::<Vec<_>>(),
+            [&IssueKind::SameCourseSelected {
+                other: "高等数学 教师".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_time_conflicts_with_selected_and_targets() {
+        let selected = class("1", "大学物理", "B01", "1-16周 星期一 第1-2节");
+        let first = class("2", "线性代数", "C01", "1-8周 星期一 第2-3节");
+        let second = class("3", "概率论", "D01", "1-16周 星期一 第3-4节");
+        let issues = check_all(&[first, second], &[selected], &Plan::default());
+        assert_eq!(
+            issues,
+            [
+                Issue {
+                    id: "2".to_string(),
+                    name: "线性代数".to_string(),
+                    kind: IssueKind::ConflictsWithSelected {
+                        other: "大学物理".to_string(),
+                        when: "星期一 第2-3节".to_string(),
+                    },
+                },
+                Issue {
+                    id: "2".to_string(),
+                    name: "线性代数".to_string(),
+                    kind: IssueKind::ConflictsWithTarget {
+                        other: "概率论".to_string(),
+                        when: "星期一 第2-3节".to_string(),
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn classes_in_the_same_group_do_not_conflict() {
+        let first = class("1", "体育", "E01", "1-16周 星期二 第3-4节");
+        let second = class("2", "体育", "E01", "1-16周 星期二 第3-4节");
+        let mut plan = Plan::default();
+        plan.assign("1", Some(0));
+        plan.assign("2", Some(0));
+        assert!(check_all(&[first.clone(), second.clone()], &[], &plan).is_empty());
+        // 不在同一组时报告冲突
+        assert_eq!(check_all(&[first, second], &[], &Plan::default()).len(), 1);
+    }
+
+    #[test]
+    fn disjoint_weeks_do_not_conflict() {
+        let odd = class("1", "英语", "F01", "1-16周(单) 星期四 第5-6节");
+        let even = class("2", "日语", "G01", "1-16周(双) 星期四 第5-6节");
+        let early = class("3", "德语", "H01", "1-8周 星期四 第5-6节");
+        let late = class("4", "法语", "I01", "9-16周 星期四 第5-6节");
+        assert!(check_all(&[odd], &[even], &Plan::default()).is_empty());
+        assert!(check_all(&[early, late], &[], &Plan::default()).is_empty());
+    }
+
+    #[test]
+    fn excluded_courses_are_not_checked() {
+        let math = [class("1", "高等数学", "A01", "1-16周 星期一 第1-2节")];
+        let mut plan = Plan::default();
+        plan.update_course("1", |course| course.include = false);
+        assert!(check_all(&math, &math, &plan).is_empty());
+    }
+}
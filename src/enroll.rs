@@ -0,0 +1,150 @@
+use crate::catalog;
+use crate::proxy::{self, ProxyError, ProxyErrorKind, RequestProgress};
+use funky_lesson_engine::{Course, Reply, Transport, TransportError, Vacancy};
+use leptos::prelude::*;
+use serde_json::Value;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// 盯课查询余量时每页的教学班数
+const VACANCY_PAGE_SIZE: u32 = 100;
+
+// 选课引擎通过代理发送选课请求
+pub struct ProxyTransport {
+    // 每次请求时读取，重新登录后换成新的 token
+    pub token: RwSignal<Option<String>>,
+    pub batch_id: String,
+}
+
+// 响应到达时记录的耗时和上游 Date 头
+#[derive(Default)]
+struct Received {
+    latency_ms: Option<u64>,
+    date_ms: Option<u64>,
+}
+
+impl ProxyTransport {
+    fn token(&self) -> String {
+        self.token.get_untracked().unwrap_or_default()
+    }
+}
+
+impl Transport for ProxyTransport {
+    async fn select(&self, course: &Course) -> Result<Reply, TransportError> {
+        let (received, on_progress) = track_response();
+        let json = proxy::select_course(
+            &self.token(),
+            &self.batch_id,
+            &course.class_type,
+            &course.id,
+            &course.secret_val,
+            on_progress,
+        )
+        .await
+        .map_err(transport_error)?;
+        Ok(reply(&json, &received.borrow()))
+    }
+
+    async fn keep_alive(&self) -> Result<Reply, TransportError> {
+        let (received, on_progress) = track_response();
+        let json = proxy::check_session(&self.token(), &self.batch_id, on_progress)
+            .await
+            .map_err(transport_error)?;
+        Ok(reply(&json, &received.borrow()))
+    }
+
+    // 按课程名搜索课程列表，逐页查找这个教学班的课容量和已选人数
+    // 所有页都没有这个教学班时返回 ClassNotFound
+    async fn vacancy(&self, course: &Course) -> Result<Vacancy, TransportError> {
+        let mut page = 1;
+        loop {
+            let (received, on_progress) = track_response();
+            let json = proxy::get_class_list(
+                &self.token(),
+                &self.batch_id,
+                &course.class_type,
+                &course.name,
+                page,
+                VACANCY_PAGE_SIZE,
+                on_progress,
+            )
+            .await
+            .map_err(transport_error)?;
+            let reply = reply(&json, &received.borrow());
+            // 登录失效等错误交给引擎按 code 处理
+            if json["code"] != 200 {
+                return Ok(Vacancy {
+                    reply,
+                    remaining: None,
+                });
+            }
+
+            let data = &json["data"];
+            let classes = catalog::flatten(&data["rows"]);
+            if let Some(class) = classes
+                .iter()
+                .find(|class| class["JXBID"].as_str() == Some(&course.id))
+            {
+                return Ok(Vacancy {
+                    reply,
+                    remaining: remaining_seats(class),
+                });
+            }
+            let total = catalog::count(&data["total"]).unwrap_or(0);
+            if data["rows"].as_array().is_none_or(Vec::is_empty)
+                || page * VACANCY_PAGE_SIZE >= total
+            {
+                return Err(TransportError::ClassNotFound);
+            }
+            page += 1;
+        }
+    }
+}
+
+// 教学班的课容量减已选人数
+fn remaining_seats(class: &Value) -> Option<u32> {
+    let capacity = catalog::count(&class["KRL"])?;
+    let enrolled = catalog::count(&class["YXRS"])?;
+    Some(capacity.saturating_sub(enrolled))
+}
+
+fn track_response() -> (Rc<RefCell<Received>>, impl FnMut(RequestProgress) + 'static) {
+    let received = Rc::new(RefCell::new(Received::default()));
+    let slot = received.clone();
+    let on_progress = move |progress| {
+        if let RequestProgress::Received {
+            elapsed_ms, date, ..
+        } = progress
+        {
+            *slot.borrow_mut() = Received {
+                latency_ms: Some(elapsed_ms),
+                date_ms: date.as_deref().and_then(parse_http_date),
+            };
+        }
+    };
+    (received, on_progress)
+}
+
+fn reply(json: &serde_json::Value, received: &Received) -> Reply {
+    Reply {
+        date_ms: received.date_ms,
+        ..Reply::from_json(json, received.latency_ms)
+    }
+}
+
+// 解析 HTTP Date 头，例如 "Mon, 01 Sep 2025 02:00:00 GMT"
+fn parse_http_date(date: &str) -> Option<u64> {
+    let ms = js_sys::Date::parse(date);
+    (ms.is_finite() && ms >= 0.0).then_some(ms as u64)
+}
+
+fn transport_error(e: ProxyError) -> TransportError {
+    match e.kind() {
+        Some(ProxyErrorKind::Timeout) => TransportError::Timeout,
+        Some(ProxyErrorKind::Connect) => TransportError::Connect,
+        Some(ProxyErrorKind::Tls) => TransportError::Tls,
+        Some(ProxyErrorKind::UpstreamHttp(code)) => TransportError::Http(*code),
+        Some(ProxyErrorKind::NonJsonBody) => TransportError::InvalidBody,
+        _ => TransportError::Other(e.to_string()),
+    }
+}
@@ -0,0 +1,375 @@
+use crate::schedule::{ClassDetails, Session};
+use crate::tauri;
+use funky_lesson_core::model::structs::CourseInfo;
+use leptos::web_sys;
+use serde_json::json;
+use std::collections::HashMap;
+use std::fmt::Write;
+use wasm_bindgen::JsCast;
+
+// 每节课的开始和结束时间（时, 分），按吉林大学的作息时间
+const PERIOD_TIMES: [((u32, u32), (u32, u32)); 11] = [
+    ((8, 0), (8, 45)),
+    ((8, 55), (9, 40)),
+    ((10, 0), (10, 45)),
+    ((10, 55), (11, 40)),
+    ((13, 30), (14, 15)),
+    ((14, 25), (15, 10)),
+    ((15, 30), (16, 15)),
+    ((16, 25), (17, 10)),
+    ((18, 30), (19, 15)),
+    ((19, 25), (20, 10)),
+    ((20, 20), (21, 5)),
+];
+
+const TIMEZONE: &str = "Asia/Shanghai";
+
+// 生成已选课程的 iCalendar 日历，每次上课安排按周重复
+// semester_start 为第一周中的任意一天 "YYYY-MM-DD"，now_ms 用于 DTSTAMP
+// 返回日历文本和没有导出的课程（没有上课时间、周次或节次超出作息表）
+pub fn calendar(
+    courses: &[CourseInfo],
+    details: &HashMap<String, ClassDetails>,
+    semester_start: &str,
+    now_ms: u64,
+) -> Option<(String, Vec<String>)> {
+    let monday = week_one_monday(parse_date(semester_start)?);
+    let stamp = format_utc(now_ms);
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//FunkyLesson//Timetable//ZH".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "BEGIN:VTIMEZONE".to_string(),
+        format!("TZID:{TIMEZONE}"),
+        "BEGIN:STANDARD".to_string(),
+        "DTSTART:19700101T000000".to_string(),
+        "TZOFFSETFROM:+0800".to_string(),
+        "TZOFFSETTO:+0800".to_string(),
+        "TZNAME:CST".to_string(),
+        "END:STANDARD".to_string(),
+        "END:VTIMEZONE".to_string(),
+    ];
+    let mut skipped = Vec::new();
+
+    for course in courses {
+        let class = details.get(&course.JXBID).cloned().unwrap_or_default();
+        // 没有写周次的安排不知道哪几周上课
+        let exportable = |s: &Session| {
+            s.weeks != u64::MAX
+                && s.first_period >= 1
+                && s.last_period as usize <= PERIOD_TIMES.len()
+        };
+        if class.sessions.is_empty() || !class.sessions.iter().all(exportable) {
+            skipped.push(course.KCM.clone());
+            continue;
+        }
+
+        for (index, session) in class.sessions.iter().enumerate() {
+            let (start_time, _) = PERIOD_TIMES[session.first_period as usize - 1];
+            let (_, end_time) = PERIOD_TIMES[session.last_period as usize - 1];
+            for (first_week, interval, count) in week_runs(session.weeks) {
+                let day = monday + (first_week as i64 - 1) * 7 + session.weekday as i64 - 1;
+                let mut description = format!(
+                    "教师: {}\\n周次: {}",
+                    escape(&course.SKJS),
+                    describe_weeks(session.weeks)
+                );
+                if !class.code.is_empty() {
+                    let _ = write!(description, "\\n课程号: {}", escape(&class.code));
+                }
+                lines.extend([
+                    "BEGIN:VEVENT".to_string(),
+                    format!("UID:{}-{index}-{first_week}@funky-lesson", course.JXBID),
+                    format!("DTSTAMP:{stamp}"),
+                    format!("DTSTART;TZID={TIMEZONE}:{}", format_local(day, start_time)),
+                    format!("DTEND;TZID={TIMEZONE}:{}", format_local(day, end_time)),
+                    format!("RRULE:FREQ=WEEKLY;INTERVAL={interval};COUNT={count}"),
+                    format!("SUMMARY:{}", escape(&course.KCM)),
+                    format!("LOCATION:{}", escape(&session.place)),
+                    format!("DESCRIPTION:{description}"),
+                    "END:VEVENT".to_string(),
+                ]);
+            }
+        }
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let text = lines.iter().map(|line| fold(line)).collect::<String>();
+    Some((text, skipped))
+}
+
+// 第一周的星期一，1970-01-01 是星期四
+fn week_one_monday(start: i64) -> i64 {
+    start - (start + 3).rem_euclid(7)
+}
+
+// 把上课的周拆成等间隔的几段 (第一周, 间隔, 次数)，单双周的间隔为 2
+fn week_runs(weeks: u64) -> Vec<(u32, u32, u32)> {
+    let weeks: Vec<u32> = (1..64).filter(|week| weeks & (1 << week) != 0).collect();
+    let mut runs = Vec::new();
+    let mut rest = weeks.as_slice();
+    while let Some(&first) = rest.first() {
+        let interval = match rest.get(1) {
+            Some(&second) if second - first <= 2 => second - first,
+            _ => 1,
+        };
+        let count = rest
+            .iter()
+            .enumerate()
+            .take_while(|&(i, &week)| week == first + i as u32 * interval)
+            .count();
+        runs.push((first, interval, count as u32));
+        rest = &rest[count..];
+    }
+    runs
+}
+
+// 例如 "1-8,10-16"
+fn describe_weeks(weeks: u64) -> String {
+    let mut ranges = Vec::new();
+    let mut week = 1;
+    while week < 64 {
+        if weeks & (1 << week) == 0 {
+            week += 1;
+            continue;
+        }
+        let first = week;
+        while week < 63 && weeks & (1 << (week + 1)) != 0 {
+            week += 1;
+        }
+        ranges.push(if first == week {
+            first.to_string()
+        } else {
+            format!("{first}-{week}")
+        });
+        week += 1;
+    }
+    ranges.join(",")
+}
+
+// 转义 TEXT 值中的特殊字符
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+// 每行不超过 75 字节，续行以空格开头，以 CRLF 结尾
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+// "YYYY-MM-DD" 转为距 1970-01-01 的天数
+fn parse_date(text: &str) -> Option<i64> {
+    let mut parts = text.trim().split('-').map(str::parse::<i64>);
+    let (year, month, day) = (
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+    );
+    if parts.next().is_some() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146097 + day_of_era - 719468)
+}
+
+// 距 1970-01-01 的天数转为 (年, 月, 日)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn format_local(days: i64, (hour, minute): (u32, u32)) -> String {
+    let (year, month, day) = civil_from_days(days);
+    format!("{year:04}{month:02}{day:02}T{hour:02}{minute:02}00")
+}
+
+fn format_utc(ms: u64) -> String {
+    let seconds = ms / 1000;
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    let time = seconds % 86400;
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+// 保存导出的文件：Tauri 中弹出系统的保存对话框，浏览器中直接下载
+// 用户在对话框中取消时返回 false
+pub async fn save(file_name: &str, contents: &str) -> Result<bool, String> {
+    if tauri::is_tauri() {
+        return tauri::invoke(
+            "save_text_file",
+            &json!({ "fileName": file_name, "contents": contents }),
+        )
+        .await
+        .map_err(|e| e.as_str().map_or_else(|| e.to_string(), str::to_string));
+    }
+
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type("text/calendar;charset=utf-8");
+    let parts = js_sys::Array::of1(&wasm_bindgen::JsValue::from_str(contents));
+    let blob = web_sys::Blob::new_with_str_sequence_and_options(&parts, &options)
+        .map_err(|e| format!("{e:?}"))?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(|e| format!("{e:?}"))?;
+    let anchor = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.create_element("a").ok())
+        .and_then(|element| element.dyn_into::<web_sys::HtmlAnchorElement>().ok())
+        .ok_or_else(|| "无法创建下载链接".to_string())?;
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+    let _ = web_sys::Url::revoke_object_url(&url);
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weeks(list: &[u32]) -> u64 {
+        list.iter().fold(0, |mask, week| mask | (1 << week))
+    }
+
+    fn course(id: &str, name: &str, teacher: &str) -> CourseInfo {
+        serde_json::from_value(json!({
+            "JXBID": id,
+            "KCM": name,
+            "SKJS": teacher,
+            "teachingClassType": "TJKC",
+            "secretVal": "",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn week_one_starts_on_monday() {
+        let monday = parse_date("2025-09-01").unwrap();
+        // 星期三、星期日和星期一本身都对齐到同一个星期一
+        for day in ["2025-09-01", "2025-09-03", "2025-09-07"] {
+            assert_eq!(week_one_monday(parse_date(day).unwrap()), monday, "{day}");
+        }
+        assert_eq!(
+            week_one_monday(parse_date("2025-09-08").unwrap()),
+            monday + 7
+        );
+        assert_eq!(civil_from_days(monday), (2025, 9, 1));
+    }
+
+    #[test]
+    fn weeks_split_into_regular_runs() {
+        assert_eq!(week_runs(weeks(&[1, 3, 5, 7, 9, 11, 13, 15])), [(1, 2, 8)]);
+        assert_eq!(week_runs(weeks(&[2, 4, 6, 8, 10, 12, 14, 16])), [(2, 2, 8)]);
+        assert_eq!(week_runs(weeks(&[1, 3, 4, 5])), [(1, 2, 2), (4, 1, 2)]);
+        let gap: Vec<u32> = (1..=8).chain(10..=16).collect();
+        assert_eq!(week_runs(weeks(&gap)), [(1, 1, 8), (10, 1, 7)]);
+        assert_eq!(week_runs(weeks(&[5])), [(5, 1, 1)]);
+        assert_eq!(describe_weeks(weeks(&gap)), "1-8,10-16");
+        assert_eq!(describe_weeks(weeks(&[1, 3, 4, 5])), "1,3-5");
+    }
+
+    #[test]
+    fn folds_at_75_octets_between_characters() {
+        let line = format!("SUMMARY:{}", "高等数学".repeat(20));
+        let folded = fold(&line);
+        assert!(folded.ends_with("\r\n"));
+        let lines: Vec<&str> = folded.trim_end_matches("\r\n").split("\r\n").collect();
+        assert!(lines.len() > 1);
+        for (index, part) in lines.iter().enumerate() {
+            assert!(part.len() <= 75, "{part}");
+            assert_eq!(index > 0, part.starts_with(' '));
+        }
+        // 去掉折行后还原为原来的内容
+        let unfolded: String = lines
+            .iter()
+            .enumerate()
+            .map(|(index, part)| if index > 0 { &part[1..] } else { part })
+            .collect();
+        assert_eq!(unfolded, line);
+        assert_eq!(fold("VERSION:2.0"), "VERSION:2.0\r\n");
+    }
+
+    #[test]
+    fn dates_round_trip_across_leap_days() {
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        for (year, month, day) in [
+            (2024, 2, 28),
+            (2024, 2, 29),
+            (2024, 3, 1),
+            (2000, 2, 29),
+            (1900, 3, 1),
+            (2100, 2, 28),
+            (2025, 12, 31),
+        ] {
+            let days = parse_date(&format!("{year}-{month:02}-{day:02}")).unwrap();
+            assert_eq!(civil_from_days(days), (year, month, day));
+        }
+        let leap = parse_date("2024-02-28").unwrap();
+        assert_eq!(civil_from_days(leap + 1), (2024, 2, 29));
+        let common = parse_date("2023-02-28").unwrap();
+        assert_eq!(civil_from_days(common + 1), (2023, 3, 1));
+        assert_eq!(parse_date("2025-13-01"), None);
+        assert_eq!(parse_date("2025-09"), None);
+        assert_eq!(format_utc(86_400_000 + 3_723_000), "19700102T010203Z");
+    }
+
+    #[test]
+    fn exports_recurring_events_and_reports_skipped_courses() {
+        let mut details = HashMap::new();
+        details.insert(
+            "1".to_string(),
+            ClassDetails::from_value(&json!({
+                "KCH": "A01",
+                "YPSJDD": "1-15周(单) 星期五 第9-11节 中心校区, 李楼",
+            })),
+        );
+        let courses = [
+            course("1", "高等数学", "张三,李四"),
+            course("2", "没有时间的课", "王五"),
+        ];
+
+        let (text, skipped) = calendar(&courses, &details, "2025-09-03", 0).unwrap();
+
+        assert_eq!(skipped, ["没有时间的课"]);
+        assert!(text.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(text.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(text.matches("BEGIN:VEVENT").count(), 1);
+        assert!(text.contains("DTSTART;TZID=Asia/Shanghai:20250905T183000\r\n"));
+        assert!(text.contains("DTEND;TZID=Asia/Shanghai:20250905T210500\r\n"));
+        assert!(text.contains("RRULE:FREQ=WEEKLY;INTERVAL=2;COUNT=8\r\n"));
+        assert!(text.contains("LOCATION:中心校区\\, 李楼\r\n"));
+        assert!(text.contains("DESCRIPTION:教师: 张三\\,李四\\n"));
+        assert!(calendar(&courses, &details, "not a date", 0).is_none());
+    }
+}
@@ -1,5 +1,14 @@
+mod catalog;
+mod conflict;
+mod enroll;
 mod external_browser;
 mod external_link;
+mod ics;
+mod plan;
+mod proxy;
+mod schedule;
+mod tauri;
+mod timetable;
 
 mod app;
 use app::*;
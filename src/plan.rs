@@ -0,0 +1,313 @@
+use leptos::web_sys;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+// localStorage 中选课计划的键前缀，后接学号和批次代码
+const STORAGE_PREFIX: &str = "funky-lesson.plan";
+
+// 一个批次的选课计划，按学号和批次保存在 WebView 的 localStorage 中
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Plan {
+    // "多选一"分组，每组按偏好顺序列出教学班 ID
+    pub groups: Vec<Vec<String>>,
+    // 按教学班 ID 保存的单门课程设置，没有保存的课程使用默认设置
+    pub courses: HashMap<String, CoursePlan>,
+    // 在课程目录中加入待选的教学班，保存上游返回的原始数据
+    pub added: Vec<Value>,
+    // 导出课表时第一周中的任意一天 "YYYY-MM-DD"
+    pub semester_start: Option<String>,
+}
+
+// 单门课程是否参与抢课及其策略
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoursePlan {
+    pub include: bool,
+    // 课容量已满时继续重试
+    pub retry_when_full: bool,
+    // 最多发送的请求数
+    pub max_attempts: Option<u32>,
+    // 放弃的时刻，Unix 毫秒时间戳
+    pub give_up_at: Option<u64>,
+}
+
+impl Default for CoursePlan {
+    fn default() -> Self {
+        Self {
+            include: true,
+            retry_when_full: true,
+            max_attempts: None,
+            give_up_at: None,
+        }
+    }
+}
+
+impl Plan {
+    // 读取保存的计划，没有保存过或无法解析时返回空计划
+    pub fn load(username: &str, batch_id: &str) -> Self {
+        storage()
+            .and_then(|storage| storage.get_item(&storage_key(username, batch_id)).ok()?)
+            .and_then(|json| match serde_json::from_str(&json) {
+                Ok(plan) => Some(plan),
+                Err(e) => {
+                    log::warn!("Ignoring invalid saved plan: {e}");
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, username: &str, batch_id: &str) {
+        let Some(storage) = storage() else {
+            return;
+        };
+        let json = serde_json::to_string(self).unwrap_or_default();
+        if let Err(e) = storage.set_item(&storage_key(username, batch_id), &json) {
+            log::warn!("Failed to save plan: {e:?}");
+        }
+    }
+
+    pub fn course(&self, id: &str) -> CoursePlan {
+        self.courses.get(id).copied().unwrap_or_default()
+    }
+
+    // 修改单门课程的设置，恢复为默认设置时不再保存
+    pub fn update_course(&mut self, id: &str, f: impl FnOnce(&mut CoursePlan)) {
+        let mut course = self.course(id);
+        f(&mut course);
+        if course == CoursePlan::default() {
+            self.courses.remove(id);
+        } else {
+            self.courses.insert(id.to_string(), course);
+        }
+    }
+
+    pub fn is_added(&self, id: &str) -> bool {
+        self.added
+            .iter()
+            .any(|course| course["JXBID"].as_str() == Some(id))
+    }
+
+    pub fn add(&mut self, course: Value) {
+        if let Some(id) = course["JXBID"].as_str()
+            && !self.is_added(id)
+        {
+            self.added.push(course);
+        }
+    }
+
+    // 只删除保存的原始数据，分组和设置保留，用于已经收藏到网站上的课程
+    pub fn forget_added(&mut self, id: &str) {
+        self.added
+            .retain(|course| course["JXBID"].as_str() != Some(id));
+    }
+
+    // 移出待选，同时删除这门课程的分组和设置
+    pub fn remove(&mut self, id: &str) {
+        self.forget_added(id);
+        self.assign(id, None);
+        self.courses.remove(id);
+    }
+
+    // 课程所在的分组和组内的顺位
+    pub fn position(&self, id: &str) -> Option<(usize, usize)> {
+        self.groups.iter().enumerate().find_map(|(group, ids)| {
+            ids.iter()
+                .position(|member| member == id)
+                .map(|rank| (group, rank))
+        })
+    }
+
+    // 把课程移到指定分组的末尾，group 不是已有分组时新建一组，None 时移出分组
+    pub fn assign(&mut self, id: &str, group: Option<usize>) {
+        let existing = self.groups.len();
+        for ids in &mut self.groups {
+            ids.retain(|member| member != id);
+        }
+        match group {
+            Some(group) if group < existing => self.groups[group].push(id.to_string()),
+            Some(_) => self.groups.push(vec![id.to_string()]),
+            None => {}
+        }
+        // 移出后变空的分组在最后删除，保证 group 指向调用时的分组
+        self.groups.retain(|ids| !ids.is_empty());
+    }
+
+    // 在组内提前一位
+    pub fn raise(&mut self, id: &str) {
+        if let Some((group, rank)) = self.position(id)
+            && rank > 0
+        {
+            self.groups[group].swap(rank - 1, rank);
+        }
+    }
+
+    // 把同组的课程排在一起并按偏好排序，返回每门课程和所在分组
+    // 分组出现在组内第一门课程原来的位置，不在 items 中的课程被忽略
+    pub fn arrange<T>(&self, items: Vec<T>, id: impl Fn(&T) -> &str) -> Vec<(T, Option<usize>)> {
+        let mut slots: Vec<Option<T>> = items.into_iter().map(Some).collect();
+        let mut arranged = Vec::with_capacity(slots.len());
+        for index in 0..slots.len() {
+            let Some(item) = slots[index].take() else {
+                continue;
+            };
+            let Some((group, _)) = self.position(id(&item)) else {
+                arranged.push((item, None));
+                continue;
+            };
+            slots[index] = Some(item);
+            for member in &self.groups[group] {
+                let found = slots
+                    .iter_mut()
+                    .find(|slot| slot.as_ref().is_some_and(|item| id(item) == member));
+                if let Some(item) = found.and_then(Option::take) {
+                    arranged.push((item, Some(group)));
+                }
+            }
+        }
+        arranged
+    }
+}
+
+fn storage_key(username: &str, batch_id: &str) -> String {
+    format!("{STORAGE_PREFIX}.{username}.{batch_id}")
+}
+
+fn storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn plan(groups: &[&[&str]]) -> Plan {
+        Plan {
+            groups: groups
+                .iter()
+                .map(|ids| ids.iter().map(|id| id.to_string()).collect())
+                .collect(),
+            ..Plan::default()
+        }
+    }
+
+    #[test]
+    fn assign_to_new_and_existing_groups() {
+        let mut plan = Plan::default();
+        plan.assign("a", Some(usize::MAX));
+        plan.assign("b", Some(usize::MAX));
+        plan.assign("c", Some(0));
+        assert_eq!(plan, self::plan(&[&["a", "c"], &["b"]]));
+        assert_eq!(plan.position("c"), Some((0, 1)));
+        assert_eq!(plan.position("b"), Some((1, 0)));
+        assert_eq!(plan.position("d"), None);
+
+        // 移到另一组时从原来的组中删除
+        plan.assign("c", Some(1));
+        assert_eq!(plan, self::plan(&[&["a"], &["b", "c"]]));
+    }
+
+    #[test]
+    fn emptied_groups_are_removed() {
+        let mut plan = plan(&[&["a"], &["b", "c"]]);
+        plan.assign("a", None);
+        assert_eq!(plan, self::plan(&[&["b", "c"]]));
+
+        // 唯一的课程移到新组时，原来的组删除后新组前移
+        let mut plan = self::plan(&[&["a"], &["b"]]);
+        plan.assign("a", Some(1));
+        assert_eq!(plan, self::plan(&[&["b", "a"]]));
+    }
+
+    #[test]
+    fn raise_moves_one_rank_up() {
+        let mut plan = plan(&[&["a", "b", "c"]]);
+        plan.raise("c");
+        assert_eq!(plan, self::plan(&[&["a", "c", "b"]]));
+        plan.raise("a");
+        assert_eq!(plan, self::plan(&[&["a", "c", "b"]]));
+        plan.raise("x");
+        assert_eq!(plan, self::plan(&[&["a", "c", "b"]]));
+    }
+
+    #[test]
+    fn arrange_keeps_groups_together_in_preference_order() {
+        let plan = plan(&[&["d", "b"], &["e"]]);
+        let arranged = plan.arrange(vec!["a", "b", "c", "d", "e", "f"], |id| id);
+        assert_eq!(
+            arranged,
+            [
+                ("a", None),
+                ("d", Some(0)),
+                ("b", Some(0)),
+                ("c", None),
+                ("e", Some(1)),
+                ("f", None),
+            ]
+        );
+        // 不在 items 中的组员被忽略
+        assert_eq!(
+            plan.arrange(vec!["b", "c"], |id| id),
+            [("b", Some(0)), ("c", None)]
+        );
+    }
+
+    #[test]
+    fn courses_default_to_included_and_defaults_are_not_stored() {
+        let mut plan = Plan::default();
+        assert_eq!(plan.course("a"), CoursePlan::default());
+        assert!(plan.course("a").include);
+
+        plan.update_course("a", |course| course.max_attempts = Some(5));
+        assert_eq!(plan.course("a").max_attempts, Some(5));
+        assert!(plan.course("a").include);
+        assert!(plan.courses.contains_key("a"));
+
+        // 恢复默认设置后不再保存
+        plan.update_course("a", |course| course.max_attempts = None);
+        assert!(plan.courses.is_empty());
+    }
+
+    #[test]
+    fn add_ignores_duplicates_and_classes_without_id() {
+        let mut plan = Plan::default();
+        plan.add(json!({ "JXBID": "a", "KCM": "数学" }));
+        plan.add(json!({ "JXBID": "a", "KCM": "数学（重复）" }));
+        plan.add(json!({ "KCM": "没有教学班 ID" }));
+        assert_eq!(plan.added.len(), 1);
+        assert!(plan.is_added("a"));
+        assert_eq!(plan.added[0]["KCM"], "数学");
+    }
+
+    #[test]
+    fn forget_added_keeps_group_and_settings() {
+        let mut plan = plan(&[&["a", "b"]]);
+        plan.add(json!({ "JXBID": "a" }));
+        plan.update_course("a", |course| course.include = false);
+
+        plan.forget_added("a");
+        assert!(!plan.is_added("a"));
+        assert_eq!(plan.position("a"), Some((0, 0)));
+        assert!(!plan.course("a").include);
+    }
+
+    #[test]
+    fn remove_drops_course_from_group_and_settings() {
+        let mut plan = plan(&[&["a", "b"], &["c"]]);
+        plan.add(json!({ "JXBID": "a" }));
+        plan.update_course("a", |course| course.retry_when_full = false);
+
+        plan.remove("a");
+        assert!(!plan.is_added("a"));
+        assert_eq!(plan.position("a"), None);
+        assert_eq!(plan.course("a"), CoursePlan::default());
+        assert_eq!(plan.groups, self::plan(&[&["b"], &["c"]]).groups);
+
+        // 组内只剩这门课程时整组删除
+        plan.remove("c");
+        assert_eq!(plan.groups, self::plan(&[&["b"]]).groups);
+    }
+}
@@ -0,0 +1,589 @@
+use crate::tauri;
+use gloo_net::http::{Request, RequestBuilder, Response};
+use leptos::task::spawn_local;
+use leptos::web_sys;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::cell::{Cell, RefCell};
+use std::fmt;
+
+// 浏览器中直接打开时默认的本地代理地址，可以通过页面地址的 ?proxy= 修改
+const DEFAULT_PROXY_ORIGIN: &str = "http://127.0.0.1:3030";
+// 与代理约定的共享密钥请求头
+const SECRET_HEADER: &str = "X-Funky-Secret";
+// 代理转发的上游 Date 头
+const UPSTREAM_DATE_HEADER: &str = "X-Upstream-Date";
+
+// 前端访问上游的方式
+// Tauri 窗口中通过 IPC 调用后端命令，浏览器中通过本地 HTTP 代理
+#[derive(Debug, Clone)]
+enum Transport {
+    Ipc,
+    Http { url: String, secret: Option<String> },
+}
+
+thread_local! {
+    static TRANSPORT: RefCell<Option<Transport>> = const { RefCell::new(None) };
+    // IPC 请求的编号，取消请求时告诉后端是哪一个
+    // 从页面加载时间开始编号，刷新页面后不会与后端残留的取消记录重复
+    static NEXT_REQUEST_ID: Cell<u64> = Cell::new(js_sys::Date::now() as u64 * 1000);
+}
+
+// 单个请求的进度，IPC 模式下 Received 中的耗时和 Date 头来自 proxy_call 的结果
+#[derive(Debug, Clone)]
+pub enum RequestProgress {
+    Sent,
+    // date 为上游响应的 Date 头
+    Received {
+        status: u16,
+        elapsed_ms: u64,
+        date: Option<String>,
+    },
+}
+
+// IPC 命令 proxy_call 的结果，date 为上游响应的 Date 头
+#[derive(Deserialize)]
+struct CallResult {
+    status: u16,
+    elapsed_ms: u64,
+    #[serde(default)]
+    date: Option<String>,
+    body: Value,
+}
+
+// 代理错误信封中的 kind
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProxyErrorKind {
+    Timeout,
+    Connect,
+    Tls,
+    // 上游返回的 HTTP 状态码
+    UpstreamHttp(u16),
+    NonJsonBody,
+    // 代理拒绝了请求（未知操作、参数错误等）
+    Rejected(String),
+}
+
+impl ProxyErrorKind {
+    fn parse(kind: &str, status: Option<u16>) -> Self {
+        match kind {
+            "timeout" => ProxyErrorKind::Timeout,
+            "connect" => ProxyErrorKind::Connect,
+            "tls" => ProxyErrorKind::Tls,
+            "upstream_http" => ProxyErrorKind::UpstreamHttp(status.unwrap_or_default()),
+            "non_json_body" => ProxyErrorKind::NonJsonBody,
+            other => ProxyErrorKind::Rejected(other.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ProxyError {
+    // 内置代理没有启动或没有响应
+    Unavailable(String),
+    // 无法连接到本地代理
+    Network(String),
+    // 代理返回的结构化错误
+    Proxy {
+        kind: ProxyErrorKind,
+        message: String,
+    },
+    // 响应无法解析
+    Decode(String),
+}
+
+impl ProxyError {
+    pub fn kind(&self) -> Option<&ProxyErrorKind> {
+        match self {
+            ProxyError::Proxy { kind, .. } => Some(kind),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ProxyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProxyError::Unavailable(msg) => write!(f, "{msg}"),
+            ProxyError::Network(msg) => write!(f, "无法连接本地代理: {msg}"),
+            ProxyError::Proxy { kind, message } => write!(f, "[{kind:?}] {message}"),
+            ProxyError::Decode(msg) => write!(f, "响应解析失败: {msg}"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ErrorEnvelope {
+    error: ErrorBody,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    kind: String,
+    message: String,
+    #[serde(default)]
+    status: Option<u16>,
+}
+
+impl From<ErrorEnvelope> for ProxyError {
+    fn from(envelope: ErrorEnvelope) -> Self {
+        if envelope.error.kind == "unavailable" {
+            return ProxyError::Unavailable(envelope.error.message);
+        }
+        ProxyError::Proxy {
+            kind: ProxyErrorKind::parse(&envelope.error.kind, envelope.error.status),
+            message: envelope.error.message,
+        }
+    }
+}
+
+fn error_from_body(text: &str) -> ProxyError {
+    match serde_json::from_str::<ErrorEnvelope>(text) {
+        Ok(envelope) => envelope.into(),
+        Err(e) => ProxyError::Decode(e.to_string()),
+    }
+}
+
+async fn read_text(response: Response) -> Result<(bool, String), ProxyError> {
+    let ok = response.ok();
+    let text = response
+        .text()
+        .await
+        .map_err(|e| ProxyError::Decode(e.to_string()))?;
+    Ok((ok, text))
+}
+
+// IPC 命令返回的错误，格式与 HTTP 代理的错误信封相同
+fn error_from_ipc(value: Value) -> ProxyError {
+    match serde_json::from_value::<ErrorEnvelope>(value.clone()) {
+        Ok(envelope) => envelope.into(),
+        Err(_) => ProxyError::Network(
+            value
+                .as_str()
+                .map_or_else(|| value.to_string(), str::to_string),
+        ),
+    }
+}
+
+// 确认后端可用，首次调用时选定传输方式并缓存
+// Tauri 中检查上游客户端是否创建成功，浏览器中使用默认代理地址
+pub async fn connect() -> Result<(), ProxyError> {
+    transport().await.map(|_| ())
+}
+
+async fn transport() -> Result<Transport, ProxyError> {
+    if let Some(transport) = TRANSPORT.with(|t| t.borrow().clone()) {
+        return Ok(transport);
+    }
+    let transport = if tauri::is_tauri() {
+        // proxy_status 以字符串返回后端启动失败的原因
+        tauri::invoke::<()>("proxy_status", &json!({}))
+            .await
+            .map_err(|e| match e.as_str() {
+                Some(message) => ProxyError::Unavailable(message.to_string()),
+                None => error_from_ipc(e),
+            })?;
+        Transport::Ipc
+    } else {
+        // 代理改了监听地址或端口时，通过 ?proxy=http://127.0.0.1:8080 指定
+        let url = query_param("proxy")
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| DEFAULT_PROXY_ORIGIN.to_string());
+        Transport::Http {
+            url,
+            secret: query_param("secret"),
+        }
+    };
+    TRANSPORT.with(|t| *t.borrow_mut() = Some(transport.clone()));
+    Ok(transport)
+}
+
+// 读取页面地址中的查询参数，值需要 URL 编码
+// 浏览器中访问设置了共享密钥的代理时，密钥通过 ?secret= 传入
+fn query_param(name: &str) -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    search
+        .trim_start_matches('?')
+        .split('&')
+        .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+        .filter(|value| !value.is_empty())
+        .and_then(|value| js_sys::decode_uri_component(value).ok())
+        .map(String::from)
+}
+
+// 构造发往 HTTP 代理的请求，带上共享密钥
+fn http_request(
+    build: fn(&str) -> RequestBuilder,
+    url: &str,
+    secret: Option<&str>,
+    path: &str,
+) -> RequestBuilder {
+    let request = build(&format!("{url}{path}"));
+    match secret {
+        Some(secret) => request.header(SECRET_HEADER, secret),
+        None => request,
+    }
+}
+
+// 调用具名操作，返回上游的 JSON
+// 上游以非 2xx 状态返回 JSON 时同样交给调用方按 code 判断
+async fn call(operation: &str, token: Option<&str>, params: Value) -> Result<Value, ProxyError> {
+    call_with_progress(operation, token, params, |_| {}).await
+}
+
+async fn call_with_progress(
+    operation: &str,
+    token: Option<&str>,
+    params: Value,
+    mut on_progress: impl FnMut(RequestProgress) + 'static,
+) -> Result<Value, ProxyError> {
+    let (url, secret) = match transport().await? {
+        Transport::Ipc => {
+            // 调用方丢弃 Future（例如停止抢课）时通知后端取消请求
+            let cancel = CancelOnDrop::new();
+            on_progress(RequestProgress::Sent);
+            let result: Result<CallResult, _> = tauri::invoke(
+                "proxy_call",
+                &json!({
+                    "operation": operation,
+                    "token": token,
+                    "params": params,
+                    "requestId": cancel.id,
+                }),
+            )
+            .await
+            .map_err(error_from_ipc);
+            cancel.finish();
+            let result = result?;
+            on_progress(RequestProgress::Received {
+                status: result.status,
+                elapsed_ms: result.elapsed_ms,
+                date: result.date,
+            });
+            return Ok(result.body);
+        }
+        Transport::Http { url, secret } => (url, secret),
+    };
+
+    let mut request = http_request(
+        Request::post,
+        &url,
+        secret.as_deref(),
+        &format!("/api/proxy/{operation}"),
+    );
+    if let Some(token) = token {
+        request = request.header("Authorization", token);
+    }
+    // 调用方丢弃 Future（例如停止抢课）时取消 fetch
+    let abort = AbortOnDrop::new();
+    let request = request
+        .abort_signal(abort.signal().as_ref())
+        .json(&params)
+        .map_err(|e| ProxyError::Decode(e.to_string()))?;
+
+    let started = js_sys::Date::now();
+    on_progress(RequestProgress::Sent);
+    let response = request
+        .send()
+        .await
+        .map_err(|e| ProxyError::Network(e.to_string()))?;
+    on_progress(RequestProgress::Received {
+        status: response.status(),
+        elapsed_ms: (js_sys::Date::now() - started) as u64,
+        date: response.headers().get(UPSTREAM_DATE_HEADER),
+    });
+
+    let (ok, text) = read_text(response).await?;
+    if !ok && let Ok(envelope) = serde_json::from_str::<ErrorEnvelope>(&text) {
+        return Err(envelope.into());
+    }
+    serde_json::from_str(&text).map_err(|e| ProxyError::Decode(e.to_string()))
+}
+
+// 离开作用域时中止关联的 fetch，对已完成的请求没有影响
+struct AbortOnDrop(Option<web_sys::AbortController>);
+
+impl AbortOnDrop {
+    fn new() -> Self {
+        Self(web_sys::AbortController::new().ok())
+    }
+
+    fn signal(&self) -> Option<web_sys::AbortSignal> {
+        self.0.as_ref().map(web_sys::AbortController::signal)
+    }
+}
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        if let Some(controller) = &self.0 {
+            controller.abort();
+        }
+    }
+}
+
+// IPC 请求完成前被丢弃时调用 proxy_cancel，后端丢弃对应的上游请求
+struct CancelOnDrop {
+    id: u64,
+    done: bool,
+}
+
+impl CancelOnDrop {
+    fn new() -> Self {
+        let id = NEXT_REQUEST_ID.with(|next| {
+            let id = next.get();
+            next.set(id + 1);
+            id
+        });
+        Self { id, done: false }
+    }
+
+    fn finish(mut self) {
+        self.done = true;
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        let id = self.id;
+        spawn_local(async move {
+            if let Err(e) = tauri::invoke::<()>("proxy_cancel", &json!({ "requestId": id })).await {
+                log::warn!("Failed to cancel request {id}: {e}");
+            }
+        });
+    }
+}
+
+// 调用 GET 操作，返回页面文本
+async fn call_page(operation: &str) -> Result<String, ProxyError> {
+    let (url, secret) = match transport().await? {
+        Transport::Ipc => {
+            let page = call(operation, None, json!({})).await?;
+            return page
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| ProxyError::Decode(format!("unexpected page response: {page}")));
+        }
+        Transport::Http { url, secret } => (url, secret),
+    };
+
+    let response = http_request(
+        Request::get,
+        &url,
+        secret.as_deref(),
+        &format!("/api/proxy/{operation}"),
+    )
+    .send()
+    .await
+    .map_err(|e| ProxyError::Network(e.to_string()))?;
+
+    let (ok, text) = read_text(response).await?;
+    if ok {
+        Ok(text)
+    } else {
+        Err(error_from_body(&text))
+    }
+}
+
+// 应用写入的日志文件路径，浏览器中运行时日志由独立代理自行输出
+pub async fn log_file_path() -> Option<String> {
+    match transport().await.ok()? {
+        Transport::Ipc => tauri::invoke("log_file_path", &json!({})).await.ok()?,
+        Transport::Http { .. } => None,
+    }
+}
+
+// 清空后端持有的上游会话 cookie，退出登录时调用
+pub async fn reset_session() -> Result<(), ProxyError> {
+    let (url, secret) = match transport().await? {
+        Transport::Ipc => {
+            return tauri::invoke::<()>("proxy_reset_session", &json!({}))
+                .await
+                .map_err(error_from_ipc);
+        }
+        Transport::Http { url, secret } => (url, secret),
+    };
+
+    let response = http_request(Request::post, &url, secret.as_deref(), "/api/session/reset")
+        .send()
+        .await
+        .map_err(|e| ProxyError::Network(e.to_string()))?;
+
+    let (ok, text) = read_text(response).await?;
+    if ok {
+        Ok(())
+    } else {
+        Err(error_from_body(&text))
+    }
+}
+
+// 获取验证码，返回 (uuid, 图片 data url)
+pub async fn get_captcha() -> Result<(String, String), ProxyError> {
+    let resp = call("captcha", None, json!({})).await?;
+    let uuid = resp["data"]["uuid"].as_str();
+    let captcha = resp["data"]["captcha"].as_str();
+    match (uuid, captcha) {
+        (Some(uuid), Some(captcha)) => Ok((uuid.to_string(), captcha.to_string())),
+        _ => Err(ProxyError::Decode(format!(
+            "unexpected captcha response: {resp}"
+        ))),
+    }
+}
+
+// 从登录页中取出 AES 密钥
+pub async fn get_aes_key() -> Result<String, ProxyError> {
+    let page = call_page("profile_page").await?;
+    parse_aes_key(&page).ok_or_else(|| ProxyError::Decode("aesKey not found".to_string()))
+}
+
+fn parse_aes_key(page: &str) -> Option<String> {
+    let rest = &page[page.find("aesKey")? + "aesKey".len()..];
+    let rest = &rest[rest.find('"')? + 1..];
+    let key = &rest[..rest.find('"')?];
+    (!key.is_empty()).then(|| key.to_string())
+}
+
+pub async fn login(
+    loginname: &str,
+    encrypted_password: &str,
+    captcha: &str,
+    uuid: &str,
+) -> Result<Value, ProxyError> {
+    call(
+        "login",
+        None,
+        json!({
+            "loginname": loginname,
+            "password": encrypted_password,
+            "captcha": captcha,
+            "uuid": uuid,
+        }),
+    )
+    .await
+}
+
+pub async fn set_batch(token: &str, batch_id: &str) -> Result<Value, ProxyError> {
+    call("set_batch", Some(token), json!({ "batch_id": batch_id })).await
+}
+
+pub async fn get_selected_courses(token: &str, batch_id: &str) -> Result<Value, ProxyError> {
+    call(
+        "selected_list",
+        Some(token),
+        json!({ "batch_id": batch_id }),
+    )
+    .await
+}
+
+pub async fn get_favorite_courses(token: &str, batch_id: &str) -> Result<Value, ProxyError> {
+    call(
+        "favorite_list",
+        Some(token),
+        json!({ "batch_id": batch_id }),
+    )
+    .await
+}
+
+// 在选课网站上收藏教学班
+pub async fn add_favorite(
+    token: &str,
+    batch_id: &str,
+    class_type: &str,
+    class_id: &str,
+    secret_val: &str,
+) -> Result<Value, ProxyError> {
+    call(
+        "favorite_add",
+        Some(token),
+        json!({
+            "batch_id": batch_id,
+            "class_type": class_type,
+            "class_id": class_id,
+            "secret_val": secret_val,
+        }),
+    )
+    .await
+}
+
+pub async fn remove_favorite(
+    token: &str,
+    batch_id: &str,
+    class_type: &str,
+    class_id: &str,
+) -> Result<Value, ProxyError> {
+    call(
+        "favorite_remove",
+        Some(token),
+        json!({
+            "batch_id": batch_id,
+            "class_type": class_type,
+            "class_id": class_id,
+        }),
+    )
+    .await
+}
+
+// 查询已选课程确认登录状态，定时开始前用来保持会话并校准服务器时钟
+pub async fn check_session(
+    token: &str,
+    batch_id: &str,
+    on_progress: impl FnMut(RequestProgress) + 'static,
+) -> Result<Value, ProxyError> {
+    call_with_progress(
+        "selected_list",
+        Some(token),
+        json!({ "batch_id": batch_id }),
+        on_progress,
+    )
+    .await
+}
+
+// 查询可选课程列表的一页，keyword 匹配课程名、课程号或教师，为空时不筛选
+pub async fn get_class_list(
+    token: &str,
+    batch_id: &str,
+    class_type: &str,
+    keyword: &str,
+    page_number: u32,
+    page_size: u32,
+    on_progress: impl FnMut(RequestProgress) + 'static,
+) -> Result<Value, ProxyError> {
+    call_with_progress(
+        "class_list",
+        Some(token),
+        json!({
+            "batch_id": batch_id,
+            "class_type": class_type,
+            "keyword": keyword,
+            "page_number": page_number,
+            "page_size": page_size,
+        }),
+        on_progress,
+    )
+    .await
+}
+
+// 选课请求，on_progress 接收请求的发送和响应事件
+pub async fn select_course(
+    token: &str,
+    batch_id: &str,
+    class_type: &str,
+    class_id: &str,
+    secret_val: &str,
+    on_progress: impl FnMut(RequestProgress) + 'static,
+) -> Result<Value, ProxyError> {
+    call_with_progress(
+        "add_course",
+        Some(token),
+        json!({
+            "batch_id": batch_id,
+            "class_type": class_type,
+            "class_id": class_id,
+            "secret_val": secret_val,
+        }),
+        on_progress,
+    )
+    .await
+}
@@ -0,0 +1,246 @@
+// 解析上游返回的上课时间地点 (YPSJDD)，例如 "1-16周 星期三 第3-4节 逸夫楼B203"
+// 多次上课之间用分号分隔，无法识别的部分当作地点
+
+use serde_json::Value;
+
+// CourseInfo 中没有的课程号和上课时间，从上游返回的原始数据中取出
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClassDetails {
+    // 课程号 (KCH)，同一门课的不同教学班相同
+    pub code: String,
+    pub schedule: String,
+    pub sessions: Vec<Session>,
+}
+
+impl ClassDetails {
+    pub fn from_value(value: &Value) -> Self {
+        let schedule = value["YPSJDD"]
+            .as_str()
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        Self {
+            code: value["KCH"].as_str().unwrap_or_default().to_string(),
+            sessions: parse(&schedule),
+            schedule,
+        }
+    }
+
+    // 与另一个教学班第一次同时上课的安排
+    pub fn overlap(&self, other: &ClassDetails) -> Option<&Session> {
+        self.sessions
+            .iter()
+            .find(|session| other.sessions.iter().any(|o| session.overlaps(o)))
+    }
+}
+
+// 一次上课安排
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    // 第 n 周上课时第 n 位为 1
+    pub weeks: u64,
+    // 星期一为 1，星期日为 7
+    pub weekday: u8,
+    // 开始和结束的节次，包含两端
+    pub first_period: u8,
+    pub last_period: u8,
+    pub place: String,
+}
+
+impl Session {
+    pub fn has_period(&self, period: u8) -> bool {
+        (self.first_period..=self.last_period).contains(&period)
+    }
+
+    // 在同一周的同一天有重叠的节次
+    pub fn overlaps(&self, other: &Session) -> bool {
+        self.weeks & other.weeks != 0
+            && self.weekday == other.weekday
+            && self.first_period <= other.last_period
+            && other.first_period <= self.last_period
+    }
+
+    // 例如 "星期三 第3-4节"
+    pub fn describe(&self) -> String {
+        let periods = if self.first_period == self.last_period {
+            self.first_period.to_string()
+        } else {
+            format!("{}-{}", self.first_period, self.last_period)
+        };
+        format!("{} 第{periods}节", weekday_name(self.weekday))
+    }
+}
+
+// 只返回能识别出星期和节次的上课安排
+pub fn parse(text: &str) -> Vec<Session> {
+    text.split([';', '；', '\n'])
+        .filter_map(parse_session)
+        .collect()
+}
+
+fn parse_session(text: &str) -> Option<Session> {
+    let mut weeks = None;
+    let mut weekday = None;
+    let mut periods = None;
+    let mut place = Vec::new();
+    for token in text.split_whitespace() {
+        if weekday.is_none()
+            && let Some(day) = parse_weekday(token)
+        {
+            weekday = Some(day);
+        } else if periods.is_none()
+            && let Some(range) = parse_periods(token)
+        {
+            periods = Some(range);
+        } else if weeks.is_none()
+            && let Some(mask) = parse_weeks(token)
+        {
+            weeks = Some(mask);
+        } else {
+            place.push(token);
+        }
+    }
+    let (first_period, last_period) = periods?;
+    Some(Session {
+        // 没有写周次时按整个学期处理
+        weeks: weeks.unwrap_or(u64::MAX),
+        weekday: weekday?,
+        first_period,
+        last_period,
+        place: place.join(" "),
+    })
+}
+
+// "星期三"、"周三"
+fn parse_weekday(token: &str) -> Option<u8> {
+    let day = token
+        .strip_prefix("星期")
+        .or_else(|| token.strip_prefix('周'))?;
+    ["一", "二", "三", "四", "五", "六", "日"]
+        .iter()
+        .position(|name| day == *name)
+        .or_else(|| (day == "天").then_some(6))
+        .map(|index| index as u8 + 1)
+}
+
+// "第3-4节"、"3-4节"、"第5节"
+fn parse_periods(token: &str) -> Option<(u8, u8)> {
+    let range = token.strip_suffix('节')?;
+    let range = range.strip_prefix('第').unwrap_or(range);
+    let (first, last) = parse_range(range)?;
+    Some((u8::try_from(first).ok()?, u8::try_from(last).ok()?))
+}
+
+// "1-16周"、"1-8,10-16周"、"1-16周(单)"
+fn parse_weeks(token: &str) -> Option<u64> {
+    let (ranges, parity) = match token.split_once('周') {
+        Some((ranges, rest)) => (ranges, rest.trim_matches(['(', ')', '（', '）'])),
+        None => return None,
+    };
+    let mut mask = 0u64;
+    for range in ranges.split([',', '，']) {
+        let (first, last) = parse_range(range)?;
+        for week in first..=last.min(63) {
+            let keep = match parity {
+                "单" => week % 2 == 1,
+                "双" => week % 2 == 0,
+                _ => true,
+            };
+            if keep {
+                mask |= 1 << week;
+            }
+        }
+    }
+    (mask != 0).then_some(mask)
+}
+
+// "3-4" 或 "5"
+fn parse_range(text: &str) -> Option<(u32, u32)> {
+    let (first, last) = match text.split_once('-') {
+        Some((first, last)) => (first.trim().parse().ok()?, last.trim().parse().ok()?),
+        None => {
+            let single = text.trim().parse().ok()?;
+            (single, single)
+        }
+    };
+    (first <= last).then_some((first, last))
+}
+
+pub fn weekday_name(weekday: u8) -> &'static str {
+    match weekday {
+        1 => "星期一",
+        2 => "星期二",
+        3 => "星期三",
+        4 => "星期四",
+        5 => "星期五",
+        6 => "星期六",
+        _ => "星期日",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn weeks(list: impl IntoIterator<Item = u32>) -> u64 {
+        list.into_iter().fold(0, |mask, week| mask | (1 << week))
+    }
+
+    #[test]
+    fn parses_a_single_session() {
+        assert_eq!(
+            parse("1-16周 星期三 第3-4节 逸夫楼B203"),
+            [Session {
+                weeks: weeks(1..=16),
+                weekday: 3,
+                first_period: 3,
+                last_period: 4,
+                place: "逸夫楼B203".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn parses_odd_and_even_weeks() {
+        let odd = parse("1-16周(单) 周一 第1-2节");
+        assert_eq!(odd[0].weeks, weeks([1, 3, 5, 7, 9, 11, 13, 15]));
+        let even = parse("1-16周（双） 星期日 5节");
+        assert_eq!(even[0].weeks, weeks([2, 4, 6, 8, 10, 12, 14, 16]));
+        assert_eq!(even[0].weekday, 7);
+        assert_eq!((even[0].first_period, even[0].last_period), (5, 5));
+        assert_eq!(
+            parse("1-8,10-16周 星期二 第1-2节")[0].weeks,
+            weeks((1..=8).chain(10..=16))
+        );
+    }
+
+    #[test]
+    fn parses_several_sessions() {
+        let sessions = parse("1-8周 星期一 第1-2节 A101；9-16周 星期三 第5-6节 B202;星期五 第7节");
+        assert_eq!(sessions.len(), 3);
+        assert_eq!(sessions[0].place, "A101");
+        assert_eq!((sessions[1].weekday, sessions[1].first_period), (3, 5));
+        assert_eq!(sessions[1].weeks, weeks(9..=16));
+        // 没有写周次时按整个学期处理
+        assert_eq!(sessions[2].weeks, u64::MAX);
+        assert_eq!(sessions[2].describe(), "星期五 第7节");
+    }
+
+    #[test]
+    fn skips_sessions_without_weekday_or_periods() {
+        assert!(parse("").is_empty());
+        assert!(parse("1-16周 逸夫楼B203").is_empty());
+        assert!(parse("星期三 逸夫楼").is_empty());
+    }
+
+    #[test]
+    fn overlap_needs_a_common_week() {
+        let odd = ClassDetails::from_value(&json!({ "YPSJDD": "1-16周(单) 星期三 第3-4节" }));
+        let even = ClassDetails::from_value(&json!({ "YPSJDD": "1-16周(双) 星期三 第3-4节" }));
+        let all = ClassDetails::from_value(&json!({ "YPSJDD": "1-16周 星期三 第4-5节" }));
+        assert_eq!(odd.overlap(&even), None);
+        assert!(odd.overlap(&all).is_some());
+        assert!(even.overlap(&all).is_some());
+    }
+}
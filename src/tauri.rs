@@ -0,0 +1,33 @@
+use leptos::web_sys;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+// withGlobalTauri 开启后注入的 window.__TAURI__.core
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(catch, js_namespace = ["window", "__TAURI__", "core"], js_name = invoke)]
+    async fn tauri_invoke(cmd: &str, args: JsValue) -> Result<JsValue, JsValue>;
+}
+
+// 检查是否运行在 Tauri 窗口中，浏览器中直接打开时返回 false
+pub fn is_tauri() -> bool {
+    web_sys::window()
+        .map(|window| js_sys::Reflect::has(&window.into(), &JsValue::from_str("__TAURI__")))
+        .and_then(Result::ok)
+        .unwrap_or(false)
+}
+
+// 调用 Tauri 命令，命令返回的错误原样转换为 JSON
+// 参数按 JSON 对象传给 invoke，serde_json::Map 默认会被转换成 JS Map
+pub async fn invoke<T: DeserializeOwned>(cmd: &str, args: &impl Serialize) -> Result<T, Value> {
+    let args = args
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(|e| Value::String(e.to_string()))?;
+    let value = tauri_invoke(cmd, args).await.map_err(|e| {
+        serde_wasm_bindgen::from_value(e.clone())
+            .unwrap_or_else(|_| Value::String(format!("{e:?}")))
+    })?;
+    serde_wasm_bindgen::from_value(value).map_err(|e| Value::String(e.to_string()))
+}
@@ -0,0 +1,249 @@
+use crate::app::{AppState, toast_error, toast_success, toast_warning};
+use crate::ics;
+use crate::schedule::{self, Session};
+use funky_lesson_engine::CourseStatus;
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+
+// 课表默认显示的节次数，有更晚的课时自动加长
+const DEFAULT_PERIODS: u8 = 11;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    // 已选课程
+    Selected,
+    // 本次抢课已经选上的待选课程
+    Succeeded,
+    // 还在抢的待选课程
+    Target,
+    // 同组已选或已放弃，不会再选上
+    Dropped,
+}
+
+impl Kind {
+    fn class(self) -> &'static str {
+        match self {
+            Kind::Selected => "bg-green-500/30 border-green-400/40 text-white",
+            Kind::Succeeded => "bg-green-400/60 border-green-200 text-white font-bold",
+            Kind::Target => "bg-blue-500/30 border-blue-400/40 text-white/90",
+            Kind::Dropped => "bg-white/5 border-white/10 text-white/40 line-through",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    id: String,
+    name: String,
+    kind: Kind,
+    schedule: String,
+    sessions: Vec<Session>,
+}
+
+// 已选课程和参与抢课的待选课程组成的周课表，抢课过程中选上的课程高亮显示
+#[component]
+pub fn Timetable(app_state: AppState) -> impl IntoView {
+    let entries = Memo::new(move |_| {
+        let details = app_state.class_details.get();
+        let plan = app_state.plan.get();
+        let snapshot = app_state.enrollment_status.get();
+        let entry = |id: &str, name: &str, kind| {
+            let details = details.get(id).cloned().unwrap_or_default();
+            Entry {
+                id: id.to_string(),
+                name: name.to_string(),
+                kind,
+                schedule: details.schedule,
+                sessions: details.sessions,
+            }
+        };
+
+        let mut entries: Vec<Entry> = app_state
+            .selected_courses
+            .get()
+            .iter()
+            .map(|c| entry(&c.JXBID, &c.KCM, Kind::Selected))
+            .collect();
+        for course in app_state.favorite_courses.get() {
+            if !plan.course(&course.JXBID).include || entries.iter().any(|e| e.id == course.JXBID) {
+                continue;
+            }
+            let status = snapshot
+                .courses
+                .iter()
+                .find(|state| state.course.id == course.JXBID)
+                .map(|state| &state.status);
+            let kind = match status {
+                Some(CourseStatus::Selected | CourseStatus::AlreadySelected) => Kind::Succeeded,
+                Some(CourseStatus::Skipped | CourseStatus::GaveUp) => Kind::Dropped,
+                _ => Kind::Target,
+            };
+            entries.push(entry(&course.JXBID, &course.KCM, kind));
+        }
+        entries
+    });
+
+    let periods = move || {
+        entries.with(|entries| {
+            entries
+                .iter()
+                .flat_map(|e| &e.sessions)
+                .map(|s| s.last_period)
+                .max()
+                .unwrap_or(0)
+                .max(DEFAULT_PERIODS)
+        })
+    };
+    // 周末没有课时不显示
+    let days = move || {
+        let weekend = entries.with(|entries| {
+            entries
+                .iter()
+                .flat_map(|e| &e.sessions)
+                .any(|s| s.weekday > 5)
+        });
+        if weekend { 7 } else { 5 }
+    };
+
+    let cell = move |weekday: u8, period: u8| {
+        entries.with(|entries| {
+            entries
+                .iter()
+                .filter_map(|e| {
+                    let session = e
+                        .sessions
+                        .iter()
+                        .find(|s| s.weekday == weekday && s.has_period(period))?;
+                    let title = format!("{} {}", e.name, e.schedule);
+                    let label = if session.first_period == period {
+                        e.name.clone()
+                    } else {
+                        "〃".to_string()
+                    };
+                    Some(view! {
+                        <div
+                            class=format!("px-1 mb-0.5 rounded border truncate {}", e.kind.class())
+                            title=title
+                        >
+                            {label}
+                        </div>
+                    })
+                })
+                .collect_view()
+        })
+    };
+
+    let unscheduled = move || {
+        entries.with(|entries| {
+            entries
+                .iter()
+                .filter(|e| e.sessions.is_empty())
+                .map(|e| e.name.clone())
+                .collect::<Vec<_>>()
+                .join("、")
+        })
+    };
+
+    // 把已选课程导出为 .ics 日历，学期开始日期按批次保存
+    let semester_start = move || app_state.plan.with(|plan| plan.semester_start.clone());
+    let exporting = RwSignal::new(false);
+    let export = move |_| {
+        let Some(start) = semester_start() else {
+            return;
+        };
+        let courses = app_state.selected_courses.get_untracked();
+        let exported = app_state.class_details.with_untracked(|details| {
+            ics::calendar(&courses, details, &start, js_sys::Date::now() as u64)
+        });
+        let Some((calendar, skipped)) = exported else {
+            toast_error("学期开始日期格式不正确");
+            return;
+        };
+        exporting.set(true);
+        spawn_local(async move {
+            match ics::save("课表.ics", &calendar).await {
+                Ok(true) if skipped.is_empty() => toast_success("课表已导出"),
+                Ok(true) => toast_warning(format!(
+                    "课表已导出，以下课程没有可识别的上课时间: {}",
+                    skipped.join("、")
+                )),
+                Ok(false) => {}
+                Err(e) => toast_error(format!("导出课表失败：{e}")),
+            }
+            exporting.set(false);
+        });
+    };
+
+    view! {
+        <div class="bg-black/30 backdrop-blur-sm rounded-xl p-4 border border-white/20 mt-4">
+            <div class="flex flex-wrap items-center gap-2 mb-3">
+                <div class="w-3 h-3 bg-yellow-400 rounded-full"></div>
+                <h3 class="text-lg font-bold text-white">"课表"</h3>
+                <span class="px-1 rounded border text-xs bg-green-500/30 border-green-400/40 text-white">"已选"</span>
+                <span class="px-1 rounded border text-xs bg-green-400/60 border-green-200 text-white font-bold">"本次选上"</span>
+                <span class="px-1 rounded border text-xs bg-blue-500/30 border-blue-400/40 text-white/90">"待选"</span>
+                <span class="px-1 rounded border text-xs bg-white/5 border-white/10 text-white/40 line-through">"不再尝试"</span>
+                <div class="flex items-center gap-2 ml-auto text-xs text-white/80">
+                    <label title="第一周中的任意一天">"学期开始"</label>
+                    <input
+                        type="date"
+                        class="bg-white/10 border border-white/20 rounded px-2 py-1 text-white text-xs"
+                        prop:value=move || semester_start().unwrap_or_default()
+                        on:change=move |ev| {
+                            let value = event_target_value(&ev);
+                            app_state.update_plan(|plan| plan.semester_start = (!value.is_empty()).then_some(value));
+                        }
+                    />
+                    <button
+                        type="button"
+                        class="bg-yellow-500/60 hover:bg-yellow-600/60 text-white rounded px-2 py-1 disabled:opacity-50"
+                        title="把已选课程导出为日历文件，可以导入手机或电脑的日历"
+                        on:click=export
+                        disabled=move || {
+                            exporting.get() || semester_start().is_none()
+                                || app_state.selected_courses.with(Vec::is_empty)
+                        }
+                    >
+                        "导出 .ics"
+                    </button>
+                </div>
+            </div>
+            <div class="overflow-x-auto">
+                <table class="w-full table-fixed text-xs text-white/80 border-collapse">
+                    <thead>
+                        <tr>
+                            <th class="w-10"></th>
+                            {move || {
+                                (1..=days())
+                                    .map(|day| view! { <th class="py-1 font-medium">{schedule::weekday_name(day)}</th> })
+                                    .collect_view()
+                            }}
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {move || {
+                            (1..=periods())
+                                .map(|period| {
+                                    view! {
+                                        <tr class="border-t border-white/10">
+                                            <td class="py-1 text-center text-white/50">{period}</td>
+                                            {(1..=days())
+                                                .map(|day| view! { <td class="p-0.5 align-top">{move || cell(day, period)}</td> })
+                                                .collect_view()}
+                                        </tr>
+                                    }
+                                })
+                                .collect_view()
+                        }}
+                    </tbody>
+                </table>
+            </div>
+            {move || {
+                let names = unscheduled();
+                (!names.is_empty()).then(|| view! {
+                    <div class="mt-2 text-xs text-white/50">{format!("没有上课时间: {names}")}</div>
+                })
+            }}
+        </div>
+    }
+}